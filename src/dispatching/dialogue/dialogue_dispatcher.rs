@@ -6,7 +6,10 @@ use crate::dispatching::{
 };
 use std::{fmt::Debug, marker::PhantomData};
 
-use futures::{future::BoxFuture, FutureExt, StreamExt};
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt, StreamExt,
+};
 use tokio::sync::mpsc;
 
 use crate::dispatching::dialogue::InMemStorageError;
@@ -21,34 +24,65 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 /// instance of this dispatcher into the [`Dispatcher`]'s methods.
 ///
 /// Note that when the storage methods [`Storage::remove_dialogue`] and
-/// [`Storage::update_dialogue`] are failed, the errors are logged, but a result
-/// from [`Storage::get_dialogue`] is provided to a user handler as-is so you
-/// can respond to a concrete user with an error description.
+/// [`Storage::update_dialogue`] are failed, the errors are passed into
+/// [`DialogueDispatcher::on_storage_error`] (by default, they are logged) and
+/// the stored dialogue stays unchanged, but a result from
+/// [`Storage::get_dialogue`] is provided to a user handler as-is so you can
+/// respond to a concrete user with an error description.
 ///
 /// See the [module-level documentation](crate::dispatching::dialogue) for the
 /// design overview.
 ///
 /// [`Dispatcher`]: crate::dispatching::Dispatcher
 /// [`DispatcherHandler`]: crate::dispatching::DispatcherHandler
-pub struct DialogueDispatcher<R, D, S, H, Upd> {
+pub struct DialogueDispatcher<R, D, S, H, Upd>
+where
+    S: Storage<D>,
+{
     storage: Arc<S>,
     handler: Arc<H>,
     _phantom: PhantomData<Mutex<D>>,
 
-    /// A lock-free map to handle updates from the same chat sequentially, but
-    /// concurrently from different chats.
+    /// A lock-free map to handle updates with the same key (the chat ID by
+    /// default) sequentially, but concurrently with different keys.
     ///
     /// A value is the TX part of an unbounded asynchronous MPSC channel. A
-    /// handler that executes updates from the same chat ID sequentially
-    /// handles the RX part.
-    senders: Arc<HashMap<i64, mpsc::UnboundedSender<UpdateWithCx<R, Upd>>>>,
+    /// handler that executes updates with the same key sequentially handles
+    /// the RX part.
+    senders: Arc<Senders<R, Upd>>,
+
+    /// Determines the key of an update for `senders`.
+    distribution_function: Arc<DistributionFunction<Upd>>,
+
+    /// Called with a chat ID when storing a dialogue of this chat fails.
+    on_storage_error: Arc<StorageErrorHandler<S::Error>>,
+
+    /// Clones an update before it's passed into the handler, so that it can be
+    /// redispatched. `None` unless [`DialogueDispatcher::redispatching`] is
+    /// called.
+    clone_update: Option<Arc<UpdateCloner<R, Upd>>>,
+}
+
+type Senders<R, Upd> = HashMap<i64, mpsc::UnboundedSender<UpdateWithCx<R, Upd>>>;
+
+type DistributionFunction<Upd> = dyn Fn(&Upd) -> Option<i64> + Send + Sync;
+
+type StorageErrorHandler<E> = dyn Fn(i64, E) + Send + Sync;
+
+type UpdateCloner<R, Upd> = dyn Fn(&UpdateWithCx<R, Upd>) -> UpdateWithCx<R, Upd> + Send + Sync;
+
+fn log_storage_error<E>(chat_id: i64, error: E)
+where
+    E: Debug,
+{
+    log::error!("Failed to store the dialogue of the chat {}: {:?}", chat_id, error);
 }
 
 impl<R, D, H, Upd> DialogueDispatcher<R, D, InMemStorage<D>, H, Upd>
 where
     H: DialogueDispatcherHandler<R, Upd, D, InMemStorageError> + Send + Sync + 'static,
     Upd: GetChatId + Send + 'static,
-    D: Default + Send + 'static,
+    D: Default + Clone + Send + 'static,
 {
     /// Creates a dispatcher with the specified `handler` and [`InMemStorage`]
     /// (a default storage).
@@ -60,6 +94,9 @@ where
             storage: InMemStorage::new(),
             handler: Arc::new(handler),
             senders: Arc::new(HashMap::new()),
+            distribution_function: Arc::new(|upd: &Upd| Some(upd.chat_id())),
+            on_storage_error: Arc::new(log_storage_error),
+            clone_update: None,
             _phantom: PhantomData,
         }
     }
@@ -80,12 +117,95 @@ where
             storage,
             handler: Arc::new(handler),
             senders: Arc::new(HashMap::new()),
+            distribution_function: Arc::new(|upd: &Upd| Some(upd.chat_id())),
+            on_storage_error: Arc::new(log_storage_error),
+            clone_update: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Returns the current dialogue of the chat with `chat_id` without
+    /// triggering a transition.
+    ///
+    /// The dialogue is read from the same storage this dispatcher uses. Since
+    /// the dispatcher itself is moved into [`Dispatcher`], clone it beforehand
+    /// to keep access to this method.
+    ///
+    /// [`Dispatcher`]: crate::dispatching::Dispatcher
+    pub async fn peek(&self, chat_id: i64) -> Result<Option<D>, S::Error> {
+        Arc::clone(&self.storage).get_dialogue(chat_id).await
+    }
+
+    /// Overrides the dialogue of the chat with `chat_id`, e.g. to get a user
+    /// out of a stuck dialogue.
+    ///
+    /// The dialogue is written to the same storage this dispatcher uses. Note
+    /// that if an update from this chat is being handled at the moment, the
+    /// dialogue may be overwritten once again by the handler's transition.
+    pub async fn force_set(&self, chat_id: i64, dialogue: D) -> Result<(), S::Error> {
+        Arc::clone(&self.storage).update_dialogue(chat_id, dialogue).await
+    }
+
+    /// Sets a function which determines what updates are handled
+    /// sequentially.
+    ///
+    /// Updates with the same key returned by `f` are handled one by one, in
+    /// the order they were received, and updates with different keys are
+    /// handled concurrently. If `f` returns `None`, an update is handled
+    /// concurrently with all the others.
+    ///
+    /// By default, updates are handled sequentially per chat, i.e. `f` is
+    /// `|upd| Some(upd.chat_id())`.
+    ///
+    /// ## Note
+    /// Dialogues are still stored per chat. If you choose a key which allows
+    /// updates from the same chat to be handled concurrently (e.g. a user
+    /// identifier in group chats), these updates also can concurrently
+    /// overwrite the dialogue of this chat.
+    #[must_use]
+    pub fn distribution_function<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Upd) -> Option<i64> + Send + Sync + 'static,
+    {
+        self.distribution_function = Arc::new(f);
+        self
+    }
+
+    /// Sets a function which is called when storing a dialogue fails.
+    ///
+    /// `f` receives the ID of the chat and the error of
+    /// [`Storage::update_dialogue`] or [`Storage::remove_dialogue`]. The
+    /// dialogue in the storage stays as it was before the update, so the
+    /// conversation is not lost.
+    ///
+    /// By default, the errors are logged.
+    #[must_use]
+    pub fn on_storage_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(i64, S::Error) + Send + Sync + 'static,
+    {
+        self.on_storage_error = Arc::new(f);
+        self
+    }
+
+    /// Enables [`DialogueStage::NextAndRedispatch`].
+    ///
+    /// To be redispatched, an update is cloned before it's passed into the
+    /// handler, so this requires `R: Clone` and `Upd: Clone` and costs a clone
+    /// per call of the handler. Without this, [`DialogueStage::NextAndRedispatch`]
+    /// is treated as [`DialogueStage::Next`], and an error is logged.
+    #[must_use]
+    pub fn redispatching(mut self) -> Self
+    where
+        R: Clone + 'static,
+        Upd: Clone,
+    {
+        self.clone_update = Some(Arc::new(UpdateWithCx::clone));
+        self
+    }
+
     #[must_use]
-    fn new_tx(&self) -> mpsc::UnboundedSender<UpdateWithCx<R, Upd>>
+    fn new_tx(&self, key: i64) -> mpsc::UnboundedSender<UpdateWithCx<R, Upd>>
     where
         R: Requester + Send + 'static,
     {
@@ -94,39 +214,122 @@ where
         let storage = Arc::clone(&self.storage);
         let handler = Arc::clone(&self.handler);
         let senders = Arc::clone(&self.senders);
+        let on_storage_error = Arc::clone(&self.on_storage_error);
+        let clone_update = self.clone_update.clone();
 
         tokio::spawn(UnboundedReceiverStream::new(rx).for_each(move |cx: UpdateWithCx<R, Upd>| {
-            let storage = Arc::clone(&storage);
-            let handler = Arc::clone(&handler);
-            let senders = Arc::clone(&senders);
+            handle_update(
+                Arc::clone(&storage),
+                Arc::clone(&handler),
+                Arc::clone(&senders),
+                Arc::clone(&on_storage_error),
+                clone_update.clone(),
+                Some(key),
+                cx,
+            )
+        }));
 
-            async move {
-                let chat_id = cx.update.chat_id();
+        tx
+    }
+}
 
-                let dialogue =
-                    Arc::clone(&storage).get_dialogue(chat_id).await.map(Option::unwrap_or_default);
+/// Handles a single update, using the dialogue of its chat.
+///
+/// `key` is the key of the worker which handles the update, if any. The worker
+/// is stopped if the dialogue exits.
+async fn handle_update<R, D, S, H, Upd>(
+    storage: Arc<S>,
+    handler: Arc<H>,
+    senders: Arc<Senders<R, Upd>>,
+    on_storage_error: Arc<StorageErrorHandler<S::Error>>,
+    clone_update: Option<Arc<UpdateCloner<R, Upd>>>,
+    key: Option<i64>,
+    cx: UpdateWithCx<R, Upd>,
+) where
+    H: DialogueDispatcherHandler<R, Upd, D, S::Error> + Send + Sync + 'static,
+    Upd: GetChatId + Send + 'static,
+    D: Default + Send + 'static,
+    S: Storage<D> + Send + Sync + 'static,
+    S::Error: Debug + Send + 'static,
+    R: Send + 'static,
+{
+    let chat_id = cx.update.chat_id();
+
+    let mut dialogue =
+        Arc::clone(&storage).get_dialogue(chat_id).await.map(Option::unwrap_or_default);
+    let mut redispatches = 0;
+    let mut cx = Some(cx);
+
+    let stage = loop {
+        let (this_cx, next_cx) = match (cx.take(), &clone_update) {
+            (Some(cx), Some(clone_update)) => (clone_update(&cx), Some(cx)),
+            (Some(cx), None) => (cx, None),
+            (None, _) => unreachable!("an update is redispatched only if it was cloned"),
+        };
+
+        match Arc::clone(&handler).handle(DialogueWithCx { cx: this_cx, dialogue }).await {
+            DialogueStage::NextAndRedispatch(new_dialogue) if next_cx.is_none() => {
+                log::error!(
+                    "An update can't be redispatched, because \
+                     `DialogueDispatcher::redispatching` wasn't called, storing the dialogue \
+                     without redispatching"
+                );
+                break DialogueStage::Next(new_dialogue);
+            }
+            DialogueStage::NextAndRedispatch(new_dialogue)
+                if redispatches < DialogueStage::<D>::MAX_REDISPATCHES =>
+            {
+                redispatches += 1;
+                dialogue = Ok(new_dialogue);
+                cx = next_cx;
+            }
+            DialogueStage::NextAndRedispatch(new_dialogue) => {
+                log::error!(
+                    "An update was redispatched {} times in a row, storing the last dialogue \
+                     without redispatching",
+                    redispatches
+                );
+                break DialogueStage::Next(new_dialogue);
+            }
+            stage => break stage,
+        }
+    };
 
-                match handler.handle(DialogueWithCx { cx, dialogue }).await {
-                    DialogueStage::Next(new_dialogue) => {
-                        if let Err(e) = storage.update_dialogue(chat_id, new_dialogue).await {
-                            log::error!("Storage::update_dialogue failed: {:?}", e);
-                        }
-                    }
-                    DialogueStage::Exit => {
-                        // On the next .poll() call, the spawned future will
-                        // return Poll::Ready, because we are dropping the
-                        // sender right here:
-                        senders.pin().remove(&chat_id);
-
-                        if let Err(e) = storage.remove_dialogue(chat_id).await {
-                            log::error!("Storage::remove_dialogue failed: {:?}", e);
-                        }
-                    }
-                }
+    match stage {
+        DialogueStage::Next(new_dialogue) | DialogueStage::NextAndRedispatch(new_dialogue) => {
+            if let Err(e) = storage.update_dialogue(chat_id, new_dialogue).await {
+                on_storage_error(chat_id, e);
+            }
+        }
+        DialogueStage::Exit => {
+            // On the next .poll() call, the spawned future will return
+            // Poll::Ready, because we are dropping the sender right here:
+            if let Some(key) = key {
+                senders.pin().remove(&key);
             }
-        }));
 
-        tx
+            if let Err(e) = storage.remove_dialogue(chat_id).await {
+                on_storage_error(chat_id, e);
+            }
+        }
+    }
+}
+
+/// Clones share the storage, the handler and the chats being handled.
+impl<R, D, S, H, Upd> Clone for DialogueDispatcher<R, D, S, H, Upd>
+where
+    S: Storage<D>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            storage: Arc::clone(&self.storage),
+            handler: Arc::clone(&self.handler),
+            senders: Arc::clone(&self.senders),
+            distribution_function: Arc::clone(&self.distribution_function),
+            on_storage_error: Arc::clone(&self.on_storage_error),
+            clone_update: self.clone_update.clone(),
+            _phantom: PhantomData,
+        }
     }
 }
 
@@ -151,9 +354,24 @@ where
         UnboundedReceiverStream::new(updates)
             .for_each(move |cx| {
                 let this = Arc::clone(&this);
-                let chat_id = cx.update.chat_id();
 
-                match this.senders.pin().get(&chat_id) {
+                let key = match (this.distribution_function)(&cx.update) {
+                    Some(key) => key,
+                    None => {
+                        tokio::spawn(handle_update(
+                            Arc::clone(&this.storage),
+                            Arc::clone(&this.handler),
+                            Arc::clone(&this.senders),
+                            Arc::clone(&this.on_storage_error),
+                            this.clone_update.clone(),
+                            None,
+                            cx,
+                        ));
+                        return future::ready(());
+                    }
+                };
+
+                match this.senders.pin().get(&key) {
                     // An old dialogue
                     Some(tx) => {
                         if tx.send(cx).is_err() {
@@ -161,15 +379,15 @@ where
                         }
                     }
                     None => {
-                        let tx = this.new_tx();
+                        let tx = this.new_tx(key);
                         if tx.send(cx).is_err() {
                             panic!("We are not dropping a receiver or call .close() on it",);
                         }
-                        this.senders.pin().insert(chat_id, tx);
+                        this.senders.pin().insert(key, tx);
                     }
                 }
 
-                async {}
+                future::ready(())
             })
             .boxed()
     }
@@ -260,7 +478,7 @@ mod tests {
                 MyUpdate::new(3, 1611),
             ]
             .into_iter()
-            .map(|update| UpdateWithCx { update, requester: Bot::new("Doesn't matter here") })
+            .map(|update| UpdateWithCx::new(Bot::new("Doesn't matter here"), update))
             .collect::<Vec<UpdateWithCx<Bot, MyUpdate>>>(),
         );
 
@@ -287,4 +505,209 @@ mod tests {
         assert_eq!(*SEQ2.lock().await, vec![411, 515, 623, 2222, 737, 10, 55456]);
         assert_eq!(*SEQ3.lock().await, vec![72782, 2737, 5475, 1096, 872, 5665, 1611]);
     }
+
+    #[tokio::test]
+    async fn redispatch_passes_update_into_new_state() {
+        #[derive(Debug, Clone)]
+        struct MyUpdate(u32);
+
+        impl GetChatId for MyUpdate {
+            fn chat_id(&self) -> i64 {
+                1
+            }
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default)]
+        enum State {
+            #[default]
+            A,
+            B,
+        }
+
+        lazy_static! {
+            static ref REPLIES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+        }
+
+        let storage = InMemStorage::new();
+        let dispatcher = DialogueDispatcher::with_storage(
+            |cx: DialogueWithCx<Bot, MyUpdate, State, InMemStorageError>| async move {
+                match cx.dialogue.unwrap() {
+                    State::A => DialogueStage::NextAndRedispatch(State::B),
+                    State::B => {
+                        REPLIES.lock().await.push(cx.cx.update.0);
+                        DialogueStage::Next(State::B)
+                    }
+                }
+            },
+            Arc::clone(&storage),
+        )
+        .redispatching();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), MyUpdate(42))).unwrap();
+        drop(tx);
+
+        dispatcher.handle(rx).await;
+
+        // Wait until our futures to be finished.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(*REPLIES.lock().await, vec![42]);
+        assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some(State::B));
+    }
+
+    #[tokio::test]
+    async fn peek_and_force_set() {
+        #[derive(Debug)]
+        struct MyUpdate(u32);
+
+        impl GetChatId for MyUpdate {
+            fn chat_id(&self) -> i64 {
+                1
+            }
+        }
+
+        let dispatcher = DialogueDispatcher::new(
+            |cx: DialogueWithCx<Bot, MyUpdate, u32, InMemStorageError>| async move {
+                DialogueStage::Next(cx.dialogue.unwrap() + cx.cx.update.0)
+            },
+        );
+        let admin = dispatcher.clone();
+
+        assert_eq!(admin.peek(1).await.unwrap(), None);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), MyUpdate(42))).unwrap();
+        drop(tx);
+
+        dispatcher.handle(rx).await;
+
+        // Wait until our futures to be finished.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(admin.peek(1).await.unwrap(), Some(42));
+        // Peeking doesn't change the dialogue.
+        assert_eq!(admin.peek(1).await.unwrap(), Some(42));
+
+        admin.force_set(1, 0).await.unwrap();
+        assert_eq!(admin.peek(1).await.unwrap(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn distribution_function_serializes_by_key() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Debug)]
+        struct MyUpdate {
+            chat_id: i64,
+            user_id: i64,
+        }
+
+        impl GetChatId for MyUpdate {
+            fn chat_id(&self) -> i64 {
+                self.chat_id
+            }
+        }
+
+        lazy_static! {
+            static ref IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+            static ref MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+            static ref HANDLED: AtomicUsize = AtomicUsize::new(0);
+        }
+
+        let dispatcher = DialogueDispatcher::new(
+            |_: DialogueWithCx<Bot, MyUpdate, (), InMemStorageError>| async move {
+                let in_flight = IN_FLIGHT.fetch_add(1, Ordering::SeqCst) + 1;
+                MAX_IN_FLIGHT.fetch_max(in_flight, Ordering::SeqCst);
+
+                tokio::time::sleep(Duration::from_millis(100)).await;
+
+                IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+                HANDLED.fetch_add(1, Ordering::SeqCst);
+                DialogueStage::Next(())
+            },
+        )
+        .distribution_function(|upd: &MyUpdate| Some(upd.user_id));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        // The same user writes into different chats.
+        for chat_id in 1..=2 {
+            let update = MyUpdate { chat_id, user_id: 42 };
+            tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        }
+        drop(tx);
+
+        dispatcher.handle(rx).await;
+
+        // Wait until our futures to be finished.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+        assert_eq!(MAX_IN_FLIGHT.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn storage_errors_are_passed_to_callback() {
+        struct FailingStorage;
+
+        impl Storage<()> for FailingStorage {
+            type Error = &'static str;
+
+            fn remove_dialogue(
+                self: Arc<Self>,
+                _chat_id: i64,
+            ) -> BoxFuture<'static, Result<(), Self::Error>> {
+                Box::pin(async { Err("Redis is down") })
+            }
+
+            fn update_dialogue(
+                self: Arc<Self>,
+                _chat_id: i64,
+                _dialogue: (),
+            ) -> BoxFuture<'static, Result<(), Self::Error>> {
+                Box::pin(async { Err("Redis is down") })
+            }
+
+            fn get_dialogue(
+                self: Arc<Self>,
+                _chat_id: i64,
+            ) -> BoxFuture<'static, Result<Option<()>, Self::Error>> {
+                Box::pin(async { Ok(None) })
+            }
+        }
+
+        #[derive(Debug)]
+        struct MyUpdate;
+
+        impl GetChatId for MyUpdate {
+            fn chat_id(&self) -> i64 {
+                42
+            }
+        }
+
+        lazy_static! {
+            static ref ERRORS: std::sync::Mutex<Vec<(i64, &'static str)>> =
+                std::sync::Mutex::new(Vec::new());
+        }
+
+        let dispatcher =
+            DialogueDispatcher::with_storage(
+                |_: DialogueWithCx<Bot, MyUpdate, (), &'static str>| async move {
+                    DialogueStage::Next(())
+                },
+                Arc::new(FailingStorage),
+            )
+            .on_storage_error(|chat_id, error| ERRORS.lock().unwrap().push((chat_id, error)));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), MyUpdate)).unwrap();
+        drop(tx);
+
+        dispatcher.handle(rx).await;
+
+        // Wait until our futures to be finished.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(*ERRORS.lock().unwrap(), vec![(42, "Redis is down")]);
+    }
 }
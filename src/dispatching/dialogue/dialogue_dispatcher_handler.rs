@@ -4,10 +4,18 @@ use std::{future::Future, sync::Arc};
 
 /// An asynchronous handler of an update used in [`DialogueDispatcher`].
 ///
+/// This trait is implemented for all functions and closures which take
+/// [`DialogueWithCx`] and return a future of [`DialogueStage`], so you can pass
+/// an `async fn` or an `async` closure directly to [`DialogueDispatcher::new`].
+/// If your transition returns [`TransitionOut`], handle the error and unwrap
+/// it into [`DialogueStage`] in such a function.
+///
 /// See [the module-level documentation for the design
 /// overview](crate::dispatching::dialogue).
 ///
 /// [`DialogueDispatcher`]: crate::dispatching::dialogue::DialogueDispatcher
+/// [`DialogueDispatcher::new`]: crate::dispatching::dialogue::DialogueDispatcher::new
+/// [`TransitionOut`]: crate::dispatching::dialogue::TransitionOut
 pub trait DialogueDispatcherHandler<R, Upd, D, E> {
     #[must_use]
     fn handle(
@@ -38,3 +46,41 @@ where
         Box::pin(async move { self(cx).await })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teloxide_core::{types::Message, Bot};
+    use tokio::sync::mpsc;
+
+    use crate::{
+        dispatching::{
+            dialogue::{DialogueDispatcher, InMemStorage, InMemStorageError, Storage},
+            DispatcherHandler, UpdateWithCx,
+        },
+        utils::MessageBuilder,
+    };
+
+    async fn count(cx: DialogueWithCx<Bot, Message, u32, InMemStorageError>) -> DialogueStage<u32> {
+        DialogueStage::Next(cx.dialogue.unwrap() + 1)
+    }
+
+    #[tokio::test]
+    async fn async_fn_is_handler() {
+        let storage = InMemStorage::new();
+        let dispatcher = DialogueDispatcher::with_storage(count, Arc::clone(&storage));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let update = MessageBuilder::new().chat(1).build();
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        drop(tx);
+
+        dispatcher.handle(rx).await;
+
+        // Wait until our futures to be finished.
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        assert_eq!(storage.get_dialogue(1).await.unwrap(), Some(1));
+    }
+}
@@ -7,9 +7,58 @@ use crate::dispatching::dialogue::TransitionOut;
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 pub enum DialogueStage<D> {
     Next(D),
+
+    /// Like [`DialogueStage::Next`], but the current update is immediately
+    /// passed into the handler once again, with the new dialogue state.
+    ///
+    /// This requires [`DialogueDispatcher::redispatching`] to be called.
+    ///
+    /// To prevent infinite loops, an update is redispatched at most
+    /// [`DialogueStage::MAX_REDISPATCHES`] times in a row. After that, the
+    /// new state is stored as if [`DialogueStage::Next`] was returned.
+    ///
+    /// [`DialogueDispatcher::redispatching`]: crate::dispatching::dialogue::DialogueDispatcher::redispatching
+    NextAndRedispatch(D),
+
     Exit,
 }
 
+impl<D> DialogueStage<D> {
+    /// The maximum number of times a single update can be redispatched via
+    /// [`DialogueStage::NextAndRedispatch`].
+    pub const MAX_REDISPATCHES: usize = 16;
+
+    /// Transforms the carried dialogue state, leaving [`DialogueStage::Exit`]
+    /// untouched.
+    #[must_use]
+    pub fn map<E, F>(self, f: F) -> DialogueStage<E>
+    where
+        F: FnOnce(D) -> E,
+    {
+        match self {
+            Self::Next(d) => DialogueStage::Next(f(d)),
+            Self::NextAndRedispatch(d) => DialogueStage::NextAndRedispatch(f(d)),
+            Self::Exit => DialogueStage::Exit,
+        }
+    }
+
+    /// Replaces the stage with the one returned by `f` from the carried
+    /// dialogue state, leaving [`DialogueStage::Exit`] untouched.
+    ///
+    /// Note that `f` is called for [`DialogueStage::NextAndRedispatch`] as
+    /// well, and whether to redispatch is then decided by the returned stage.
+    #[must_use]
+    pub fn and_then<E, F>(self, f: F) -> DialogueStage<E>
+    where
+        F: FnOnce(D) -> DialogueStage<E>,
+    {
+        match self {
+            Self::Next(d) | Self::NextAndRedispatch(d) => f(d),
+            Self::Exit => DialogueStage::Exit,
+        }
+    }
+}
+
 /// Returns a new dialogue state.
 ///
 /// Note the `Dialogue: From<State>` constraint. It means that you don't need to
@@ -28,6 +77,21 @@ where
     Ok(DialogueStage::Next(Dialogue::from(new_state)))
 }
 
+/// Returns a new dialogue state and makes the current update to be processed
+/// once again in this state.
+///
+/// This is useful when a state has nothing to ask a user about, e.g. when
+/// skipping an empty prompt. See [`DialogueStage::NextAndRedispatch`].
+///
+/// See [the module-level documentation for the design
+/// overview](crate::dispatching::dialogue).
+pub fn redispatch<Dialogue, State, E>(new_state: State) -> TransitionOut<Dialogue, E>
+where
+    Dialogue: From<State>,
+{
+    Ok(DialogueStage::NextAndRedispatch(Dialogue::from(new_state)))
+}
+
 /// Exits a dialogue.
 ///
 /// See [the module-level documentation for the design
@@ -35,3 +99,34 @@ where
 pub fn exit<D, E>() -> TransitionOut<D, E> {
     Ok(DialogueStage::Exit)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map() {
+        assert_eq!(DialogueStage::Next(1).map(|d| d + 1), DialogueStage::Next(2));
+        assert_eq!(
+            DialogueStage::NextAndRedispatch(1).map(|d| d.to_string()),
+            DialogueStage::NextAndRedispatch("1".to_owned())
+        );
+        assert_eq!(DialogueStage::<i32>::Exit.map(|d| d + 1), DialogueStage::Exit);
+    }
+
+    #[test]
+    fn and_then() {
+        assert_eq!(
+            DialogueStage::Next(1).and_then(|d| DialogueStage::Next(d + 1)),
+            DialogueStage::Next(2)
+        );
+        assert_eq!(
+            DialogueStage::Next(1).and_then(|_| DialogueStage::<i32>::Exit),
+            DialogueStage::Exit
+        );
+        assert_eq!(
+            DialogueStage::<i32>::Exit.and_then(|_| -> DialogueStage<i32> { unreachable!() }),
+            DialogueStage::Exit
+        );
+    }
+}
@@ -3,26 +3,28 @@
 //! There are three main components:
 //!
 //!  1. Your type `D` (typically an enumeration), implementing [`Transition`].
-//! It is essentially a [FSM]: its variants are possible dialogue states and
-//! [`Transition::react`] is a transition function.
+//!     It is essentially a [FSM]: its variants are possible dialogue states and
+//!     [`Transition::react`] is a transition function.
 //!
 //!  2. State types, forming `D`. They implement [`Subtransition`].
 //!
 //!  2. [`Storage<D>`], which encapsulates all the dialogues.
 //!
 //!  3. [`DialogueDispatcher`], which encapsulates your handler, [`Storage<D>`],
-//! and implements [`DispatcherHandler`].
+//!     and implements [`DispatcherHandler`].
 //!
 //! You pass [`DialogueDispatcher`] into [`Dispatcher`]. Every time
 //! [`Dispatcher`] sees an incoming input, it is transferred into
 //! [`DialogueDispatcher`], and the following steps are executed:
 //!
 //!  1. If a storage doesn't contain a dialogue from this chat, supply
-//! `D::default()` into you handler, otherwise, supply the saved dialogue
-//! from this chat.
+//!     `D::default()` into you handler, otherwise, supply the saved dialogue
+//!     from this chat.
 //!  2. If a handler has returned [`DialogueStage::Exit`], remove the dialogue
-//! from the storage, otherwise ([`DialogueStage::Next`]) force the storage to
-//! update the dialogue.
+//!     from the storage, otherwise ([`DialogueStage::Next`]) force the storage to
+//!     update the dialogue. If a handler has returned
+//!     [`DialogueStage::NextAndRedispatch`], the same input is passed into the
+//!     handler once again with the new dialogue.
 //!
 //! To avoid boilerplate, teloxide exports these convenient things: the [`next`]
 //! and [`exit`] functions, and `#[derive(BotDialogue)]` with
@@ -132,6 +134,8 @@
 //! [`DialogueStage::Exit`]:
 //! crate::dispatching::dialogue::DialogueStage::Exit
 //! [`DialogueStage::Next`]: crate::dispatching::dialogue::DialogueStage::Next
+//! [`DialogueStage::NextAndRedispatch`]:
+//! crate::dispatching::dialogue::DialogueStage::NextAndRedispatch
 //!
 //! [`up!`]: crate::up
 //! [`next`]: crate::dispatching::dialogue::next
@@ -157,11 +161,12 @@ mod transition;
 
 pub use dialogue_dispatcher::DialogueDispatcher;
 pub use dialogue_dispatcher_handler::DialogueDispatcherHandler;
-pub use dialogue_stage::{exit, next, DialogueStage};
+pub use dialogue_stage::{exit, next, redispatch, DialogueStage};
 pub use dialogue_with_cx::DialogueWithCx;
 pub use get_chat_id::GetChatId;
 pub use transition::{
     Subtransition, SubtransitionOutputType, Transition, TransitionIn, TransitionOut,
+    TransitionOutExt,
 };
 
 #[cfg(feature = "macros")]
@@ -175,4 +180,11 @@ pub use storage::{RedisStorage, RedisStorageError};
 #[cfg(feature = "sqlite-storage")]
 pub use storage::{SqliteStorage, SqliteStorageError};
 
-pub use storage::{serializer, InMemStorage, InMemStorageError, Serializer, Storage, TraceStorage};
+#[cfg(feature = "file-storage")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "file-storage")))]
+pub use storage::{FileStorage, FileStorageError};
+
+pub use storage::{
+    serializer, InMemStorage, InMemStorageError, Serializer, Storage, StorageBatch, StorageCas,
+    TraceStorage,
+};
@@ -0,0 +1,204 @@
+use super::{serializer::Serializer, Storage};
+use futures::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+/// An error returned from [`FileStorage`].
+#[derive(Debug, Error)]
+pub enum FileStorageError<SE>
+where
+    SE: Debug + Display,
+{
+    #[error("parsing/serializing error: {0}")]
+    SerdeError(SE),
+
+    #[error("an I/O error: {0}")]
+    IoError(#[from] io::Error),
+
+    /// Returned from [`FileStorage::remove_dialogue`].
+    #[error("row not found")]
+    DialogueNotFound,
+}
+
+/// A persistent dialogue storage based on a single file.
+///
+/// All the dialogues are kept in memory and the whole map of them is
+/// serialized into the file on each change. The file is written atomically (a
+/// temporary file is written and then renamed), so it's never left corrupted
+/// on a crash. This is suitable only for small bots; for larger ones use e.g.
+/// [`SqliteStorage`].
+///
+/// A serializer must be able to serialize `HashMap<i64, D>`, e.g.
+/// [`serializer::Json`].
+///
+/// [`SqliteStorage`]: crate::dispatching::dialogue::SqliteStorage
+/// [`serializer::Json`]: crate::dispatching::dialogue::serializer::Json
+pub struct FileStorage<S, D> {
+    path: PathBuf,
+    serializer: S,
+    map: Mutex<HashMap<i64, D>>,
+}
+
+impl<S, D> FileStorage<S, D>
+where
+    S: Serializer<HashMap<i64, D>>,
+    S::Error: Debug + Display,
+{
+    /// Opens a storage in the file at `path`, loading the dialogues from it.
+    ///
+    /// If the file doesn't exist, the storage is empty and the file is created
+    /// on the first change.
+    pub async fn open<P>(path: P, serializer: S) -> Result<Arc<Self>, FileStorageError<S::Error>>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        let map = match fs::read(&path).await {
+            Ok(data) => serializer.deserialize(&data).map_err(FileStorageError::SerdeError)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Arc::new(Self { path, serializer, map: Mutex::new(map) }))
+    }
+
+    async fn save(&self, map: &HashMap<i64, D>) -> Result<(), FileStorageError<S::Error>> {
+        let data = self.serializer.serialize(map).map_err(FileStorageError::SerdeError)?;
+
+        let tmp_path = tmp_path(&self.path);
+        let mut tmp = fs::File::create(&tmp_path).await?;
+        tmp.write_all(&data).await?;
+        tmp.sync_all().await?;
+
+        fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+impl<S, D> Storage<D> for FileStorage<S, D>
+where
+    S: Send + Sync + Serializer<HashMap<i64, D>> + 'static,
+    D: Clone + Send + Sync + 'static,
+    <S as Serializer<HashMap<i64, D>>>::Error: Debug + Display,
+{
+    type Error = FileStorageError<<S as Serializer<HashMap<i64, D>>>::Error>;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: i64,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let mut map = self.map.lock().await;
+            let dialogue = map.remove(&chat_id).ok_or(FileStorageError::DialogueNotFound)?;
+
+            if let Err(error) = self.save(&map).await {
+                map.insert(chat_id, dialogue);
+                return Err(error);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: i64,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let mut map = self.map.lock().await;
+            let old = map.insert(chat_id, dialogue);
+
+            if let Err(error) = self.save(&map).await {
+                match old {
+                    Some(old) => map.insert(chat_id, old),
+                    None => map.remove(&chat_id),
+                };
+                return Err(error);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: i64,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move { Ok(self.map.lock().await.get(&chat_id).cloned()) })
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    tmp_path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dispatching::dialogue::serializer::Json;
+
+    fn path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "teloxide_file_storage_{}_{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn dialogues_persist_after_reopening() {
+        let path = path("reopening");
+
+        let storage = FileStorage::<_, String>::open(&path, Json).await.unwrap();
+        Arc::clone(&storage).update_dialogue(1, "ABC".to_owned()).await.unwrap();
+        Arc::clone(&storage).update_dialogue(11, "DEF".to_owned()).await.unwrap();
+        Arc::clone(&storage).remove_dialogue(11).await.unwrap();
+        drop(storage);
+
+        let storage = FileStorage::<_, String>::open(&path, Json).await.unwrap();
+        assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("ABC".to_owned()));
+        assert_eq!(Arc::clone(&storage).get_dialogue(11).await.unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_write_keeps_old_file() {
+        let path = path("interrupted");
+
+        let storage = FileStorage::<_, String>::open(&path, Json).await.unwrap();
+        Arc::clone(&storage).update_dialogue(1, "ABC".to_owned()).await.unwrap();
+        drop(storage);
+
+        // A crash in the middle of writing leaves a partially written temporary
+        // file.
+        std::fs::write(tmp_path(&path), b"{\"1\": \"DE").unwrap();
+
+        let storage = FileStorage::<_, String>::open(&path, Json).await.unwrap();
+        assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("ABC".to_owned()));
+
+        // The next write replaces the leftover.
+        Arc::clone(&storage).update_dialogue(1, "DEF".to_owned()).await.unwrap();
+        drop(storage);
+
+        let storage = FileStorage::<_, String>::open(&path, Json).await.unwrap();
+        assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("DEF".to_owned()));
+        assert!(!tmp_path(&path).exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
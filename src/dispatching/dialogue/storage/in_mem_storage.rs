@@ -1,4 +1,4 @@
-use super::Storage;
+use super::{Storage, StorageBatch, StorageCas};
 use futures::future::BoxFuture;
 use std::{collections::HashMap, sync::Arc};
 use thiserror::Error;
@@ -71,3 +71,93 @@ where
         Box::pin(async move { Ok(self.map.lock().await.get(&chat_id).map(ToOwned::to_owned)) })
     }
 }
+
+impl<D> StorageBatch<D> for InMemStorage<D>
+where
+    D: Clone,
+    D: Send + 'static,
+{
+    fn get_dialogues(
+        self: Arc<Self>,
+        chat_ids: Vec<i64>,
+    ) -> BoxFuture<'static, Result<Vec<Option<D>>, Self::Error>> {
+        Box::pin(async move {
+            let map = self.map.lock().await;
+            Ok(chat_ids.iter().map(|chat_id| map.get(chat_id).map(ToOwned::to_owned)).collect())
+        })
+    }
+
+    fn update_dialogues(
+        self: Arc<Self>,
+        dialogues: Vec<(i64, D)>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            self.map.lock().await.extend(dialogues);
+            Ok(())
+        })
+    }
+}
+
+impl<D> StorageCas<D> for InMemStorage<D>
+where
+    D: Clone + PartialEq,
+    D: Send + 'static,
+{
+    fn compare_and_swap(
+        self: Arc<Self>,
+        chat_id: i64,
+        expected: Option<D>,
+        new: D,
+    ) -> BoxFuture<'static, Result<bool, Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let mut map = self.map.lock().await;
+            if map.get(&chat_id) != expected.as_ref() {
+                return Ok(false);
+            }
+
+            map.insert(chat_id, new);
+            Ok(true)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batch() {
+        let storage = InMemStorage::new();
+
+        Arc::clone(&storage)
+            .update_dialogues(vec![(1, "ABC"), (11, "DEF"), (256, "GHI")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Arc::clone(&storage).get_dialogues(vec![256, 2, 1, 11]).await.unwrap(),
+            vec![Some("GHI"), None, Some("ABC"), Some("DEF")]
+        );
+        assert_eq!(Arc::clone(&storage).get_dialogue(11).await.unwrap(), Some("DEF"));
+    }
+
+    #[tokio::test]
+    async fn stale_compare_and_swap_fails() {
+        let storage = InMemStorage::new();
+
+        assert!(Arc::clone(&storage).compare_and_swap(1, None, "ABC").await.unwrap());
+        let read = Arc::clone(&storage).get_dialogue(1).await.unwrap();
+
+        // Another instance changes the dialogue after we have read it.
+        assert!(Arc::clone(&storage).compare_and_swap(1, read, "DEF").await.unwrap());
+        assert!(!Arc::clone(&storage).compare_and_swap(1, read, "GHI").await.unwrap());
+
+        assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("DEF"));
+    }
+}
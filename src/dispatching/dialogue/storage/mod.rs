@@ -9,6 +9,9 @@ mod redis_storage;
 #[cfg(feature = "sqlite-storage")]
 mod sqlite_storage;
 
+#[cfg(feature = "file-storage")]
+mod file_storage;
+
 use futures::future::BoxFuture;
 
 pub use self::{
@@ -25,6 +28,10 @@ use std::sync::Arc;
 #[cfg(feature = "sqlite-storage")]
 pub use sqlite_storage::{SqliteStorage, SqliteStorageError};
 
+#[cfg(feature = "file-storage")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "file-storage")))]
+pub use file_storage::{FileStorage, FileStorageError};
+
 /// A storage of dialogues.
 ///
 /// You can implement this trait for a structure that communicates with a DB and
@@ -38,10 +45,12 @@ pub use sqlite_storage::{SqliteStorage, SqliteStorageError};
 /// - [`InMemStorage`] -- a storage based on [`std::collections::HashMap`].
 /// - [`RedisStorage`] -- a Redis-based storage.
 /// - [`SqliteStorage`] -- an SQLite-based persistent storage.
+/// - [`FileStorage`] -- a persistent storage based on a single file.
 ///
 /// [`InMemStorage`]: crate::dispatching::dialogue::InMemStorage
 /// [`RedisStorage`]: crate::dispatching::dialogue::RedisStorage
 /// [`SqliteStorage`]: crate::dispatching::dialogue::SqliteStorage
+/// [`FileStorage`]: crate::dispatching::dialogue::FileStorage
 pub trait Storage<D> {
     type Error;
 
@@ -74,3 +83,55 @@ pub trait Storage<D> {
         chat_id: i64,
     ) -> BoxFuture<'static, Result<Option<D>, Self::Error>>;
 }
+
+/// A [`Storage`] which can get and update many dialogues at once.
+///
+/// This is useful, for example, to preload dialogues of a list of chats on
+/// startup without doing a round trip to a DB for each of them.
+pub trait StorageBatch<D>: Storage<D> {
+    /// Returns the dialogues indexed by `chat_ids`, in the same order.
+    #[must_use = "Futures are lazy and do nothing unless polled with .await"]
+    fn get_dialogues(
+        self: Arc<Self>,
+        chat_ids: Vec<i64>,
+    ) -> BoxFuture<'static, Result<Vec<Option<D>>, Self::Error>>;
+
+    /// Updates the dialogues indexed by chat identifiers with the
+    /// corresponding dialogues.
+    #[must_use = "Futures are lazy and do nothing unless polled with .await"]
+    fn update_dialogues(
+        self: Arc<Self>,
+        dialogues: Vec<(i64, D)>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static;
+}
+
+/// A [`Storage`] which can update a dialogue atomically, only if it wasn't
+/// changed in the meantime.
+///
+/// This allows several bot instances to share the same storage: if two of
+/// them read the same dialogue, only the first one succeeds in storing its
+/// transition, and the other one can read the dialogue again and re-run its
+/// transition.
+///
+/// Note that [`DialogueDispatcher`] doesn't use it and just overwrites
+/// dialogues, so such a retry loop has to be implemented manually.
+///
+/// [`DialogueDispatcher`]: crate::dispatching::dialogue::DialogueDispatcher
+pub trait StorageCas<D>: Storage<D> {
+    /// Updates a dialogue indexed by `chat_id` with `new`, if the current
+    /// dialogue equals `expected` (`None` means that there's no dialogue).
+    ///
+    /// Returns `false` if the dialogue wasn't updated, because the current
+    /// dialogue differs from `expected`.
+    #[must_use = "Futures are lazy and do nothing unless polled with .await"]
+    fn compare_and_swap(
+        self: Arc<Self>,
+        chat_id: i64,
+        expected: Option<D>,
+        new: D,
+    ) -> BoxFuture<'static, Result<bool, Self::Error>>
+    where
+        D: Send + 'static;
+}
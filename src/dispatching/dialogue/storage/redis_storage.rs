@@ -1,4 +1,4 @@
-use super::{serializer::Serializer, Storage};
+use super::{serializer::Serializer, Storage, StorageBatch, StorageCas};
 use futures::future::BoxFuture;
 use redis::{AsyncCommands, IntoConnectionInfo};
 use serde::{de::DeserializeOwned, Serialize};
@@ -32,18 +32,48 @@ where
 pub struct RedisStorage<S> {
     conn: Mutex<redis::aio::Connection>,
     serializer: S,
+    prefix: Option<String>,
 }
 
 impl<S> RedisStorage<S> {
+    /// Opens a storage which uses chat IDs as keys.
     pub async fn open(
         url: impl IntoConnectionInfo,
         serializer: S,
+    ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
+        Self::open_inner(url, serializer, None).await
+    }
+
+    /// Opens a storage which uses `{prefix}:{chat_id}` as keys.
+    ///
+    /// This allows several bots to share the same Redis database without
+    /// overwriting the dialogues of each other.
+    pub async fn open_with_prefix(
+        url: impl IntoConnectionInfo,
+        serializer: S,
+        prefix: impl Into<String>,
+    ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
+        Self::open_inner(url, serializer, Some(prefix.into())).await
+    }
+
+    async fn open_inner(
+        url: impl IntoConnectionInfo,
+        serializer: S,
+        prefix: Option<String>,
     ) -> Result<Arc<Self>, RedisStorageError<Infallible>> {
         Ok(Arc::new(Self {
             conn: Mutex::new(redis::Client::open(url)?.get_async_connection().await?),
             serializer,
+            prefix,
         }))
     }
+
+    fn key(&self, chat_id: i64) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, chat_id),
+            None => chat_id.to_string(),
+        }
+    }
 }
 
 impl<S, D> Storage<D> for RedisStorage<S>
@@ -61,7 +91,7 @@ where
         Box::pin(async move {
             let deleted_rows_count = redis::pipe()
                 .atomic()
-                .del(chat_id)
+                .del(self.key(chat_id))
                 .query_async::<_, redis::Value>(self.conn.lock().await.deref_mut())
                 .await?;
 
@@ -88,7 +118,7 @@ where
         Box::pin(async move {
             let dialogue =
                 self.serializer.serialize(&dialogue).map_err(RedisStorageError::SerdeError)?;
-            self.conn.lock().await.set::<_, Vec<u8>, _>(chat_id, dialogue).await?;
+            self.conn.lock().await.set::<_, Vec<u8>, ()>(self.key(chat_id), dialogue).await?;
             Ok(())
         })
     }
@@ -101,10 +131,112 @@ where
             self.conn
                 .lock()
                 .await
-                .get::<_, Option<Vec<u8>>>(chat_id)
+                .get::<_, Option<Vec<u8>>>(self.key(chat_id))
                 .await?
                 .map(|d| self.serializer.deserialize(&d).map_err(RedisStorageError::SerdeError))
                 .transpose()
         })
     }
 }
+
+impl<S, D> StorageBatch<D> for RedisStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    fn get_dialogues(
+        self: Arc<Self>,
+        chat_ids: Vec<i64>,
+    ) -> BoxFuture<'static, Result<Vec<Option<D>>, Self::Error>> {
+        Box::pin(async move {
+            // `MGET` without keys is an error.
+            if chat_ids.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            redis::cmd("MGET")
+                .arg(chat_ids.iter().map(|&chat_id| self.key(chat_id)).collect::<Vec<_>>())
+                .query_async::<_, Vec<Option<Vec<u8>>>>(self.conn.lock().await.deref_mut())
+                .await?
+                .into_iter()
+                .map(|d| {
+                    d.map(|d| {
+                        self.serializer.deserialize(&d).map_err(RedisStorageError::SerdeError)
+                    })
+                    .transpose()
+                })
+                .collect()
+        })
+    }
+
+    fn update_dialogues(
+        self: Arc<Self>,
+        dialogues: Vec<(i64, D)>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+
+            for (chat_id, dialogue) in dialogues {
+                let dialogue =
+                    self.serializer.serialize(&dialogue).map_err(RedisStorageError::SerdeError)?;
+                pipe.set(self.key(chat_id), dialogue).ignore();
+            }
+
+            pipe.query_async::<_, ()>(self.conn.lock().await.deref_mut()).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Uses `WATCH`/`MULTI`/`EXEC`, so the dialogues are compared by their
+/// serialized values.
+impl<S, D> StorageCas<D> for RedisStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    fn compare_and_swap(
+        self: Arc<Self>,
+        chat_id: i64,
+        expected: Option<D>,
+        new: D,
+    ) -> BoxFuture<'static, Result<bool, Self::Error>> {
+        Box::pin(async move {
+            let expected = expected
+                .map(|d| self.serializer.serialize(&d).map_err(RedisStorageError::SerdeError))
+                .transpose()?;
+            let new = self.serializer.serialize(&new).map_err(RedisStorageError::SerdeError)?;
+
+            let key = self.key(chat_id);
+            let mut conn = self.conn.lock().await;
+
+            redis::cmd("WATCH").arg(&key).query_async::<_, ()>(conn.deref_mut()).await?;
+
+            let not_swapped = match conn.get::<_, Option<Vec<u8>>>(&key).await {
+                Ok(current) if current == expected => {
+                    // `EXEC` discards the `WATCH` and returns nil if the key was changed after
+                    // it.
+                    let res = redis::pipe()
+                        .atomic()
+                        .set(&key, new)
+                        .ignore()
+                        .query_async::<_, Option<()>>(conn.deref_mut())
+                        .await?;
+                    return Ok(res.is_some());
+                }
+                Ok(_) => Ok(false),
+                Err(error) => Err(error),
+            };
+
+            // The connection is shared, so the `WATCH` must be discarded even if `GET` has
+            // failed, or else it would break the next unrelated transaction.
+            let unwatched = redis::cmd("UNWATCH").query_async::<_, ()>(conn.deref_mut()).await;
+            let not_swapped = not_swapped?;
+            unwatched?;
+            Ok(not_swapped)
+        })
+    }
+}
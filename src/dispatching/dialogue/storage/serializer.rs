@@ -75,3 +75,402 @@ where
         bincode::deserialize(data)
     }
 }
+
+/// A serializer which compresses the output of an inner serializer with
+/// [Zstandard].
+///
+/// Useful for large dialogue states, e.g. to reduce memory consumption of
+/// [`RedisStorage`]. Works with any inner serializer, e.g.
+/// `Compressed<Json>` or `Compressed<Bincode>`.
+///
+/// [Zstandard]: https://facebook.github.io/zstd/
+/// [`RedisStorage`]: crate::dispatching::dialogue::RedisStorage
+#[cfg(feature = "zstd-serializer")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "zstd-serializer")))]
+pub struct Compressed<S> {
+    inner: S,
+    level: i32,
+}
+
+#[cfg(feature = "zstd-serializer")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "zstd-serializer")))]
+impl<S> Compressed<S> {
+    /// Wraps `inner` using the default compression level.
+    #[must_use]
+    pub fn new(inner: S) -> Self {
+        Self::with_level(inner, zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+
+    /// Wraps `inner` using the specified compression level.
+    ///
+    /// Zstandard supports levels from 1 up to 22; `0` means the default level.
+    #[must_use]
+    pub fn with_level(inner: S, level: i32) -> Self {
+        Self { inner, level }
+    }
+}
+
+/// An error returned from [`Compressed`].
+#[cfg(feature = "zstd-serializer")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "zstd-serializer")))]
+#[derive(Debug, thiserror::Error)]
+pub enum CompressedError<E>
+where
+    E: std::fmt::Debug + std::fmt::Display,
+{
+    #[error("parsing/serializing error: {0}")]
+    SerdeError(E),
+
+    #[error("compression error: {0}")]
+    CompressionError(#[from] std::io::Error),
+}
+
+#[cfg(feature = "zstd-serializer")]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "zstd-serializer")))]
+impl<S, D> Serializer<D> for Compressed<S>
+where
+    S: Serializer<D>,
+    S::Error: std::fmt::Debug + std::fmt::Display,
+{
+    type Error = CompressedError<S::Error>;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        let raw = self.inner.serialize(val).map_err(CompressedError::SerdeError)?;
+        Ok(zstd::encode_all(raw.as_slice(), self.level)?)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        let raw = zstd::decode_all(data)?;
+        self.inner.deserialize(&raw).map_err(CompressedError::SerdeError)
+    }
+}
+
+/// A serializer which tags the output of an inner serializer with a version of
+/// dialogue states.
+///
+/// The serialized data is prefixed with a marker (the bytes `\xFFtxv`, which
+/// can't start a JSON or CBOR value) and `version` (2 bytes, big endian). Data
+/// without the marker, e.g. stored before `Versioned` was used, is considered
+/// to be of version 0. When data with another version is deserialized, the
+/// migration function is called with the stored version and the data produced
+/// by the inner serializer. If the migration fails, the error is logged and
+/// `D::default()` is returned instead, so that users with outdated states just
+/// start their dialogues from scratch.
+///
+/// ## Example
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use teloxide::dispatching::dialogue::serializer::{Json, Serializer, Versioned};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct StateV1 {
+///     name: String,
+/// }
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct State {
+///     name: String,
+///     age: Option<u8>,
+/// }
+///
+/// let serializer = Versioned::new(Json, 2, |version, data: &[u8]| match version {
+///     0 | 1 => serde_json::from_slice(data)
+///         .map(|StateV1 { name }| State { name, age: None }),
+///     _ => serde_json::from_slice(data),
+/// });
+/// # let _: &dyn Serializer<State, Error = serde_json::Error> = &serializer;
+/// ```
+pub struct Versioned<S, M> {
+    inner: S,
+    version: u16,
+    migrate: M,
+}
+
+impl<S, M> Versioned<S, M> {
+    /// Wraps `inner`, tagging data with `version` and migrating data with other
+    /// versions using `migrate`.
+    #[must_use]
+    pub fn new(inner: S, version: u16, migrate: M) -> Self {
+        Self { inner, version, migrate }
+    }
+}
+
+impl<S, M, D> Serializer<D> for Versioned<S, M>
+where
+    S: Serializer<D>,
+    S::Error: std::fmt::Debug,
+    M: Fn(u16, &[u8]) -> Result<D, S::Error>,
+    D: Default,
+{
+    type Error = S::Error;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        let raw = self.inner.serialize(val)?;
+
+        let mut data = Vec::with_capacity(VERSIONED_MAGIC.len() + VERSION_LEN + raw.len());
+        data.extend_from_slice(VERSIONED_MAGIC);
+        data.extend_from_slice(&self.version.to_be_bytes());
+        data.extend_from_slice(&raw);
+        Ok(data)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        let (version, raw) = split_version(data).unwrap_or((0, data));
+
+        if version == self.version {
+            return self.inner.deserialize(raw);
+        }
+
+        match (self.migrate)(version, raw) {
+            Ok(val) => Ok(val),
+            Err(error) => {
+                log::warn!(
+                    "Failed to migrate a dialogue state from version {} to {}, using the default \
+                     state: {:?}",
+                    version,
+                    self.version,
+                    error
+                );
+                Ok(D::default())
+            }
+        }
+    }
+}
+
+/// Splits the data produced by [`Versioned`] into the version and the data of
+/// the inner serializer.
+fn split_version(data: &[u8]) -> Option<(u16, &[u8])> {
+    let data = data.strip_prefix(VERSIONED_MAGIC)?;
+    if data.len() < VERSION_LEN {
+        return None;
+    }
+
+    let (version, raw) = data.split_at(VERSION_LEN);
+    Some((u16::from_be_bytes([version[0], version[1]]), raw))
+}
+
+/// Tells the data of [`Versioned`] apart from data stored without a version.
+const VERSIONED_MAGIC: &[u8] = b"\xFFtxv";
+const VERSION_LEN: usize = std::mem::size_of::<u16>();
+
+/// A serializer which prefixes the output of an inner serializer with a
+/// human-readable summary of a dialogue state.
+///
+/// The summary is the [`Debug`] representation of the state, truncated to
+/// `max_len` bytes. It is stored as its length (2 bytes, big endian) followed
+/// by its UTF-8 bytes, so raw values in a storage (e.g. in Redis) can be
+/// inspected without deserializing. The summary is skipped on deserializing,
+/// so it's transparent to storage consumers.
+///
+/// [`Debug`]: std::fmt::Debug
+pub struct Tagged<S> {
+    inner: S,
+    max_len: u16,
+}
+
+impl<S> Tagged<S> {
+    /// Wraps `inner` using summaries of at most 64 bytes.
+    #[must_use]
+    pub fn new(inner: S) -> Self {
+        Self::with_max_len(inner, 64)
+    }
+
+    /// Wraps `inner` using summaries of at most `max_len` bytes.
+    #[must_use]
+    pub fn with_max_len(inner: S, max_len: u16) -> Self {
+        Self { inner, max_len }
+    }
+}
+
+/// An error returned from [`Tagged`].
+#[derive(Debug, thiserror::Error)]
+pub enum TaggedError<E>
+where
+    E: std::fmt::Debug + std::fmt::Display,
+{
+    #[error("parsing/serializing error: {0}")]
+    SerdeError(E),
+
+    /// The data doesn't start with a summary.
+    #[error("the data has no summary")]
+    NoSummary,
+}
+
+impl<S, D> Serializer<D> for Tagged<S>
+where
+    S: Serializer<D>,
+    S::Error: std::fmt::Debug + std::fmt::Display,
+    D: std::fmt::Debug,
+{
+    type Error = TaggedError<S::Error>;
+
+    fn serialize(&self, val: &D) -> Result<Vec<u8>, Self::Error> {
+        let raw = self.inner.serialize(val).map_err(TaggedError::SerdeError)?;
+
+        let summary = format!("{:?}", val);
+        let summary = truncate(&summary, self.max_len.into());
+        let summary_len = summary.len() as u16;
+
+        let mut data = Vec::with_capacity(SUMMARY_LEN_LEN + summary.len() + raw.len());
+        data.extend_from_slice(&summary_len.to_be_bytes());
+        data.extend_from_slice(summary.as_bytes());
+        data.extend_from_slice(&raw);
+        Ok(data)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<D, Self::Error> {
+        if data.len() < SUMMARY_LEN_LEN {
+            return Err(TaggedError::NoSummary);
+        }
+
+        let (summary_len, rest) = data.split_at(SUMMARY_LEN_LEN);
+        let summary_len = u16::from_be_bytes([summary_len[0], summary_len[1]]).into();
+
+        let raw = rest.get(summary_len..).ok_or(TaggedError::NoSummary)?;
+        self.inner.deserialize(raw).map_err(TaggedError::SerdeError)
+    }
+}
+
+const SUMMARY_LEN_LEN: usize = std::mem::size_of::<u16>();
+
+/// Returns the longest prefix of `s` which is at most `max_len` bytes and ends
+/// on a char boundary.
+fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "zstd-serializer")]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct State {
+        history: Vec<String>,
+    }
+
+    #[cfg(feature = "zstd-serializer")]
+    fn state() -> State {
+        State { history: vec!["The same message once again".to_owned(); 100] }
+    }
+
+    #[cfg(feature = "zstd-serializer")]
+    #[test]
+    fn compressed_roundtrip() {
+        let serializer = Compressed::new(Json);
+
+        let data = serializer.serialize(&state()).unwrap();
+        assert_eq!(Serializer::<State>::deserialize(&serializer, &data).unwrap(), state());
+    }
+
+    #[cfg(feature = "zstd-serializer")]
+    #[test]
+    fn compressed_is_smaller_than_raw() {
+        let raw = Json.serialize(&state()).unwrap();
+        let compressed = Compressed::with_level(Json, 19).serialize(&state()).unwrap();
+
+        assert!(compressed.len() < raw.len());
+    }
+
+    #[derive(Default, serde::Serialize, serde::Deserialize)]
+    struct StateV1 {
+        name: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct StateV2 {
+        name: String,
+        age: Option<u8>,
+    }
+
+    fn v2() -> Versioned<Json, impl Fn(u16, &[u8]) -> Result<StateV2, serde_json::Error>> {
+        Versioned::new(Json, 2, |version, data: &[u8]| match version {
+            0 | 1 => {
+                serde_json::from_slice(data).map(|StateV1 { name }| StateV2 { name, age: None })
+            }
+            _ => serde_json::from_slice(data),
+        })
+    }
+
+    #[test]
+    fn versioned_roundtrip() {
+        let state = StateV2 { name: "Bobby".to_owned(), age: Some(20) };
+
+        let data = v2().serialize(&state).unwrap();
+        assert_eq!(&data[..6], b"\xFFtxv\0\x02");
+        assert_eq!(v2().deserialize(&data).unwrap(), state);
+    }
+
+    #[test]
+    fn versioned_migrates_old_state() {
+        let v1 = Versioned::new(Json, 1, |_, data: &[u8]| serde_json::from_slice::<StateV1>(data));
+        let data = v1.serialize(&StateV1 { name: "Bobby".to_owned() }).unwrap();
+
+        assert_eq!(
+            v2().deserialize(&data).unwrap(),
+            StateV2 { name: "Bobby".to_owned(), age: None }
+        );
+    }
+
+    #[test]
+    fn versioned_migrates_unversioned_state() {
+        // Starts with `{"`, which must not be mistaken for a version tag.
+        let data = Json.serialize(&StateV1 { name: "Bobby".to_owned() }).unwrap();
+
+        assert_eq!(
+            v2().deserialize(&data).unwrap(),
+            StateV2 { name: "Bobby".to_owned(), age: None }
+        );
+    }
+
+    #[test]
+    fn versioned_falls_back_to_default() {
+        let mut data = b"\xFFtxv\0\x01".to_vec();
+        data.extend_from_slice(b"not a JSON");
+
+        assert_eq!(v2().deserialize(&data).unwrap(), StateV2::default());
+        assert_eq!(v2().deserialize(&[]).unwrap(), StateV2::default());
+    }
+
+    #[test]
+    fn tagged_roundtrip() {
+        let state = StateV2 { name: "Bobby".to_owned(), age: Some(20) };
+        let serializer = Tagged::new(Json);
+
+        let data = serializer.serialize(&state).unwrap();
+        assert_eq!(Serializer::<StateV2>::deserialize(&serializer, &data).unwrap(), state);
+    }
+
+    #[test]
+    fn tagged_data_contains_summary() {
+        let state = StateV2 { name: "Bobby".to_owned(), age: Some(20) };
+        let summary = r#"StateV2 { name: "Bobby", age: Some(20) }"#;
+
+        let data = Tagged::new(Json).serialize(&state).unwrap();
+        assert_eq!(&data[..2], &(summary.len() as u16).to_be_bytes());
+        assert_eq!(&data[2..2 + summary.len()], summary.as_bytes());
+
+        let data = Tagged::with_max_len(Json, 7).serialize(&state).unwrap();
+        assert_eq!(&data[..9], b"\0\x07StateV2");
+    }
+
+    #[test]
+    fn tagged_truncates_on_char_boundary() {
+        assert_eq!(truncate("Привет", 3), "П");
+        assert_eq!(truncate("Hi", 3), "Hi");
+    }
+
+    #[test]
+    fn tagged_rejects_data_without_summary() {
+        let res = Serializer::<StateV2>::deserialize(&Tagged::new(Json), &[0, 10, b'{']);
+        assert!(matches!(res, Err(TaggedError::NoSummary)));
+    }
+}
@@ -1,4 +1,4 @@
-use super::{serializer::Serializer, Storage};
+use super::{serializer::Serializer, Storage, StorageCas};
 use futures::future::BoxFuture;
 use serde::{de::DeserializeOwned, Serialize};
 use sqlx::{sqlite::SqlitePool, Executor};
@@ -123,6 +123,53 @@ where
     }
 }
 
+/// The dialogues are compared by their serialized values.
+impl<S, D> StorageCas<D> for SqliteStorage<S>
+where
+    S: Send + Sync + Serializer<D> + 'static,
+    D: Send + Serialize + DeserializeOwned + 'static,
+    <S as Serializer<D>>::Error: Debug + Display,
+{
+    fn compare_and_swap(
+        self: Arc<Self>,
+        chat_id: i64,
+        expected: Option<D>,
+        new: D,
+    ) -> BoxFuture<'static, Result<bool, Self::Error>> {
+        Box::pin(async move {
+            let new = self.serializer.serialize(&new).map_err(SqliteStorageError::SerdeError)?;
+
+            let query = match expected {
+                Some(expected) => {
+                    let expected = self
+                        .serializer
+                        .serialize(&expected)
+                        .map_err(SqliteStorageError::SerdeError)?;
+
+                    sqlx::query(
+                        "UPDATE teloxide_dialogues SET dialogue = ? WHERE chat_id = ? AND \
+                         dialogue = ?",
+                    )
+                    .bind(new)
+                    .bind(chat_id)
+                    .bind(expected)
+                }
+                None => sqlx::query(
+                    r#"
+            INSERT INTO teloxide_dialogues VALUES (?, ?)
+            ON CONFLICT(chat_id) DO NOTHING
+                    "#,
+                )
+                .bind(chat_id)
+                .bind(new),
+            };
+
+            let updated_rows_count = query.execute(&self.pool).await?.rows_affected();
+            Ok(updated_rows_count == 1)
+        })
+    }
+}
+
 async fn get_dialogue(pool: &SqlitePool, chat_id: i64) -> Result<Option<Vec<u8>>, sqlx::Error> {
     #[derive(sqlx::FromRow)]
     struct DialogueDbRow {
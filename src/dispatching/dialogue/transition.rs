@@ -60,3 +60,52 @@ pub type TransitionIn<R> = UpdateWithCx<R, Message>;
 
 /// A type returned from a FSM (sub)transition function.
 pub type TransitionOut<D, E = crate::RequestError> = Result<DialogueStage<D>, E>;
+
+/// Combinators for [`TransitionOut`].
+pub trait TransitionOutExt<D, E>: Sized {
+    /// Calls `f` with the carried dialogue state, leaving
+    /// [`DialogueStage::Exit`] and errors untouched.
+    ///
+    /// This allows to chain subtransitions: the next one runs only if the
+    /// previous one has neither exited nor failed.
+    ///
+    /// See also [`DialogueStage::and_then`].
+    fn and_then_next<D2, F>(self, f: F) -> TransitionOut<D2, E>
+    where
+        F: FnOnce(D) -> TransitionOut<D2, E>;
+}
+
+impl<D, E> TransitionOutExt<D, E> for TransitionOut<D, E> {
+    fn and_then_next<D2, F>(self, f: F) -> TransitionOut<D2, E>
+    where
+        F: FnOnce(D) -> TransitionOut<D2, E>,
+    {
+        match self? {
+            DialogueStage::Next(d) | DialogueStage::NextAndRedispatch(d) => f(d),
+            DialogueStage::Exit => Ok(DialogueStage::Exit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn and_then_next() {
+        let out: TransitionOut<i32, ()> = Ok(DialogueStage::Next(1));
+        assert_eq!(
+            out.and_then_next(|d| Ok(DialogueStage::Next(d + 1))),
+            Ok(DialogueStage::Next(2))
+        );
+
+        let out: TransitionOut<i32, ()> = Ok(DialogueStage::Exit);
+        assert_eq!(
+            out.and_then_next(|_| -> TransitionOut<i32, ()> { unreachable!() }),
+            Ok(DialogueStage::Exit)
+        );
+
+        let out: TransitionOut<i32, ()> = Err(());
+        assert_eq!(out.and_then_next(|_| -> TransitionOut<i32, ()> { unreachable!() }), Err(()));
+    }
+}
@@ -1,5 +1,7 @@
 use std::{
+    collections::HashSet,
     fmt::{self, Debug},
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc,
@@ -11,12 +13,16 @@ use crate::{
     dispatching::{
         stop_token::StopToken,
         update_listeners::{self, UpdateListener},
-        DispatcherHandler, UpdateWithCx,
+        DispatcherHandler, UpdateExt, UpdateWithCx,
     },
     error_handlers::{ErrorHandler, LoggingErrorHandler},
 };
 
-use futures::{stream::FuturesUnordered, Future, StreamExt};
+use futures::{
+    future::{self, Either},
+    stream::FuturesUnordered,
+    Future, StreamExt,
+};
 use teloxide_core::{
     requests::Requester,
     types::{
@@ -25,7 +31,9 @@ use teloxide_core::{
     },
 };
 use tokio::{
-    sync::{mpsc, Notify},
+    fs::File,
+    io::AsyncWriteExt,
+    sync::{mpsc, Notify, OwnedSemaphorePermit, Semaphore},
     task::JoinHandle,
     time::timeout,
 };
@@ -52,6 +60,14 @@ pub struct Dispatcher<R> {
     poll_answers_queue: Tx<R, PollAnswer>,
     my_chat_members_queue: Tx<R, ChatMemberUpdated>,
     chat_members_queue: Tx<R, ChatMemberUpdated>,
+    updates_queue: Tx<R, Update>,
+
+    drop_pending_updates: bool,
+    record_updates: Option<PathBuf>,
+    max_concurrent_updates: Option<Arc<Semaphore>>,
+
+    allowed_chats: Option<HashSet<i64>>,
+    unauthorized_updates_queue: Tx<R, Update>,
 
     running_handlers: FuturesUnordered<JoinHandle<()>>,
 
@@ -81,12 +97,31 @@ where
             poll_answers_queue: None,
             my_chat_members_queue: None,
             chat_members_queue: None,
+            updates_queue: None,
+            drop_pending_updates: false,
+            record_updates: None,
+            max_concurrent_updates: None,
+            allowed_chats: None,
+            unauthorized_updates_queue: None,
             running_handlers: FuturesUnordered::new(),
             state: <_>::default(),
             shutdown_notify_back: <_>::default(),
         }
     }
 
+    /// Returns the requester, which is passed to handlers inside
+    /// [`UpdateWithCx`].
+    ///
+    /// Handlers get clones of this requester. Cloning of [`Bot`] is cheap,
+    /// since its clones share the same HTTP client (and thus the connection
+    /// pool), so prefer cloning the returned requester to constructing a new
+    /// one when you need to make requests outside of handlers.
+    ///
+    /// [`Bot`]: crate::Bot
+    pub fn requester(&self) -> &R {
+        &self.requester
+    }
+
     #[must_use]
     fn new_tx<H, Upd>(&mut self, h: H) -> Tx<R, Upd>
     where
@@ -131,6 +166,101 @@ where
         self
     }
 
+    /// Drops the updates which were sent to the bot while it was offline, so
+    /// that handlers don't receive them.
+    ///
+    /// The updates are dropped via [`GetUpdates`] by [`Dispatcher::dispatch`],
+    /// before it starts polling. Custom update listeners passed to
+    /// [`Dispatcher::dispatch_with_listener`] aren't affected, since they may
+    /// not receive updates via [`GetUpdates`] at all (e.g. for a webhook, use
+    /// the `drop_pending_updates` parameter of [`SetWebhook`] instead).
+    ///
+    /// [`GetUpdates`]: crate::payloads::GetUpdates
+    /// [`SetWebhook`]: crate::payloads::SetWebhook
+    #[must_use]
+    pub fn drop_pending_updates(mut self) -> Self {
+        self.drop_pending_updates = true;
+        self
+    }
+
+    /// Appends all the received updates to the file at `path`, one
+    /// JSON-serialized update per line.
+    ///
+    /// This allows to capture updates in production and replay them locally
+    /// with [`update_listeners::from_file`]. The file is created if it doesn't
+    /// exist. If it cannot be opened, the error is logged and dispatching
+    /// goes on without recording.
+    ///
+    /// [`update_listeners::from_file`]: crate::dispatching::update_listeners::from_file
+    #[must_use]
+    pub fn record_updates<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.record_updates = Some(path.into());
+        self
+    }
+
+    /// Limits the number of updates handled at the same time.
+    ///
+    /// An update is considered handled until its [`UpdateWithCx`] (with all
+    /// its clones) is dropped. When the limit is reached, the dispatcher stops
+    /// pulling updates from the update listener, so that polling doesn't
+    /// request (and thus confirm) new updates until some of the running
+    /// handlers finish.
+    ///
+    /// If [`FnHandler::per_chat_lock`] is enabled, an update waiting for its
+    /// chat also occupies a slot, so updates from a chat are still handled in
+    /// order.
+    ///
+    /// # Panics
+    /// Panics if `limit` is zero.
+    ///
+    /// [`FnHandler::per_chat_lock`]: crate::dispatching::FnHandler::per_chat_lock
+    #[must_use]
+    pub fn max_concurrent_updates(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "`limit` must be positive");
+
+        self.max_concurrent_updates = Some(Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// Restricts dispatching to updates from `chats`.
+    ///
+    /// Before any handler runs, updates from other chats are dropped (or
+    /// passed into [`Dispatcher::unauthorized_updates_handler`], if it's
+    /// registered). Calling this function several times extends the list of
+    /// allowed chats.
+    ///
+    /// Updates which aren't bound to a chat (e.g. inline queries) are checked
+    /// by the ID of their user, which is the same as the ID of the private
+    /// chat with this user. Updates without both a chat and a user (polls) are
+    /// always dispatched.
+    ///
+    /// See also: [`UpdateExt`].
+    ///
+    /// [`UpdateExt`]: crate::dispatching::UpdateExt
+    #[must_use]
+    pub fn restrict_to_chats<I>(mut self, chats: I) -> Self
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        self.allowed_chats.get_or_insert_with(HashSet::new).extend(chats);
+        self
+    }
+
+    /// Registers a handler of updates which are dropped by
+    /// [`Dispatcher::restrict_to_chats`], e.g. to reply with a "not
+    /// authorized" message.
+    #[must_use]
+    pub fn unauthorized_updates_handler<H>(mut self, h: H) -> Self
+    where
+        H: DispatcherHandler<R, Update> + 'static + Send,
+    {
+        self.unauthorized_updates_queue = self.new_tx(h);
+        self
+    }
+
     #[must_use]
     pub fn messages_handler<H>(mut self, h: H) -> Self
     where
@@ -176,6 +306,15 @@ where
         self
     }
 
+    /// Registers a handler of [chosen inline results].
+    ///
+    /// Note that Telegram sends these updates only if [inline feedback] is
+    /// enabled for your bot via [@BotFather] (the `/setinlinefeedback`
+    /// command).
+    ///
+    /// [chosen inline results]: crate::types::ChosenInlineResult
+    /// [inline feedback]: https://core.telegram.org/bots/inline#collecting-feedback
+    /// [@BotFather]: https://t.me/botfather
     #[must_use]
     pub fn chosen_inline_results_handler<H>(mut self, h: H) -> Self
     where
@@ -248,6 +387,27 @@ where
         self
     }
 
+    /// Registers a catch-all handler, receiving whole [`Update`]s.
+    ///
+    /// An update is passed to this handler only if there is no handler
+    /// registered for its kind, e.g. with [`Dispatcher::messages_handler`]. This
+    /// allows to handle all the kinds in a single place, matching on
+    /// [`UpdateKind`] manually.
+    ///
+    /// If this handler is registered, the dispatcher hints update listeners to
+    /// receive updates of all kinds.
+    ///
+    /// [`Update`]: crate::types::Update
+    /// [`UpdateKind`]: crate::types::UpdateKind
+    #[must_use]
+    pub fn updates_handler<H>(mut self, h: H) -> Self
+    where
+        H: DispatcherHandler<R, Update> + 'static + Send,
+    {
+        self.updates_queue = self.new_tx(h);
+        self
+    }
+
     /// Starts your bot with the default parameters.
     ///
     /// The default parameters are a long polling update listener and log all
@@ -265,6 +425,10 @@ where
         R: Requester + Clone,
         <R as Requester>::GetUpdatesFaultTolerant: Send,
     {
+        if self.drop_pending_updates {
+            update_listeners::drop_pending_updates(&self.requester).await;
+        }
+
         let listener = update_listeners::polling_default(self.requester.clone()).await;
         let error_handler =
             LoggingErrorHandler::with_custom_text("An error from the update listener");
@@ -296,6 +460,11 @@ where
 
         self.hint_allowed_updates(&mut update_listener);
 
+        let mut record = match &self.record_updates {
+            Some(path) => open_record(path).await,
+            None => None,
+        };
+
         let shutdown_check_timeout = shutdown_check_timeout_for(&update_listener);
         let mut stop_token = Some(update_listener.stop_token());
 
@@ -311,12 +480,33 @@ where
             tokio::pin!(stream);
 
             loop {
-                // False positive
-                #[allow(clippy::collapsible_match)]
-                if let Ok(upd) = timeout(shutdown_check_timeout, stream.next()).await {
-                    match upd {
-                        None => break,
-                        Some(upd) => self.process_update(upd, &update_listener_error_handler).await,
+                // Don't pull the next update (and thus don't confirm it) until there is a free
+                // slot for it.
+                if let Ok(permit) = timeout(shutdown_check_timeout, self.acquire_permit()).await {
+                    let next = timeout(shutdown_check_timeout, stream.next());
+                    let shutting_down = self.state.shutting_down.notified();
+                    futures::pin_mut!(next, shutting_down);
+
+                    // Don't wait for the timeout if a shutdown is requested.
+                    let next = match future::select(next, shutting_down).await {
+                        Either::Left((next, _)) => next.ok(),
+                        Either::Right(((), _)) => None,
+                    };
+
+                    // False positive
+                    #[allow(clippy::collapsible_match)]
+                    if let Some(upd) = next {
+                        match upd {
+                            None => break,
+                            Some(upd) => {
+                                if let (Some(file), Ok(update)) = (&mut record, &upd) {
+                                    record_update(file, update).await;
+                                }
+
+                                self.process_update(upd, permit, &update_listener_error_handler)
+                                    .await
+                            }
+                        }
                     }
                 }
 
@@ -353,9 +543,20 @@ where
         }
     }
 
+    async fn acquire_permit(&self) -> Option<Arc<OwnedSemaphorePermit>> {
+        match &self.max_concurrent_updates {
+            Some(semaphore) => {
+                let permit = Arc::clone(semaphore).acquire_owned().await;
+                Some(Arc::new(permit.expect("The semaphore is never closed")))
+            }
+            None => None,
+        }
+    }
+
     async fn process_update<ListenerE, Eh>(
         &self,
         update: Result<Update, ListenerE>,
+        permit: Option<Arc<OwnedSemaphorePermit>>,
         update_listener_error_handler: &Arc<Eh>,
     ) where
         R: Requester + Clone,
@@ -373,134 +574,196 @@ where
                 }
             };
 
+            if !self.is_allowed(&update) {
+                send(&self.requester, &self.unauthorized_updates_queue, update, permit, "Update");
+                return;
+            }
+
+            if !self.has_handler_for(&update.kind) {
+                send(&self.requester, &self.updates_queue, update, permit, "Update");
+                return;
+            }
+
             match update.kind {
-                UpdateKind::Message(message) => {
-                    send(&self.requester, &self.messages_queue, message, "UpdateKind::Message")
-                }
+                UpdateKind::Message(message) => send(
+                    &self.requester,
+                    &self.messages_queue,
+                    message,
+                    permit,
+                    "UpdateKind::Message",
+                ),
                 UpdateKind::EditedMessage(message) => send(
                     &self.requester,
                     &self.edited_messages_queue,
                     message,
+                    permit,
                     "UpdateKind::EditedMessage",
                 ),
                 UpdateKind::ChannelPost(post) => send(
                     &self.requester,
                     &self.channel_posts_queue,
                     post,
+                    permit,
                     "UpdateKind::ChannelPost",
                 ),
                 UpdateKind::EditedChannelPost(post) => send(
                     &self.requester,
                     &self.edited_channel_posts_queue,
                     post,
+                    permit,
                     "UpdateKind::EditedChannelPost",
                 ),
                 UpdateKind::InlineQuery(query) => send(
                     &self.requester,
                     &self.inline_queries_queue,
                     query,
+                    permit,
                     "UpdateKind::InlineQuery",
                 ),
                 UpdateKind::ChosenInlineResult(result) => send(
                     &self.requester,
                     &self.chosen_inline_results_queue,
                     result,
+                    permit,
                     "UpdateKind::ChosenInlineResult",
                 ),
                 UpdateKind::CallbackQuery(query) => send(
                     &self.requester,
                     &self.callback_queries_queue,
                     query,
+                    permit,
                     "UpdateKind::CallbackQuer",
                 ),
                 UpdateKind::ShippingQuery(query) => send(
                     &self.requester,
                     &self.shipping_queries_queue,
                     query,
+                    permit,
                     "UpdateKind::ShippingQuery",
                 ),
                 UpdateKind::PreCheckoutQuery(query) => send(
                     &self.requester,
                     &self.pre_checkout_queries_queue,
                     query,
+                    permit,
                     "UpdateKind::PreCheckoutQuery",
                 ),
                 UpdateKind::Poll(poll) => {
-                    send(&self.requester, &self.polls_queue, poll, "UpdateKind::Poll")
+                    send(&self.requester, &self.polls_queue, poll, permit, "UpdateKind::Poll")
                 }
                 UpdateKind::PollAnswer(answer) => send(
                     &self.requester,
                     &self.poll_answers_queue,
                     answer,
+                    permit,
                     "UpdateKind::PollAnswer",
                 ),
                 UpdateKind::MyChatMember(chat_member_updated) => send(
                     &self.requester,
                     &self.my_chat_members_queue,
                     chat_member_updated,
+                    permit,
                     "UpdateKind::MyChatMember",
                 ),
                 UpdateKind::ChatMember(chat_member_updated) => send(
                     &self.requester,
                     &self.chat_members_queue,
                     chat_member_updated,
+                    permit,
                     "UpdateKind::MyChatMember",
                 ),
             }
         }
     }
 
-    fn hint_allowed_updates<E>(&self, listener: &mut impl UpdateListener<E>) {
-        fn hint_handler_allowed_update<T>(
-            queue: &Option<T>,
-            kind: AllowedUpdate,
-        ) -> std::option::IntoIter<AllowedUpdate> {
-            queue.as_ref().map(|_| kind).into_iter()
+    fn is_allowed(&self, update: &Update) -> bool {
+        let allowed_chats = match &self.allowed_chats {
+            Some(allowed_chats) => allowed_chats,
+            None => return true,
+        };
+
+        match update.chat_id().or_else(|| update.sender().map(|user| user.id)) {
+            Some(id) => allowed_chats.contains(&id),
+            None => true,
+        }
+    }
+
+    fn has_handler_for(&self, kind: &UpdateKind) -> bool {
+        match kind {
+            UpdateKind::Message(_) => self.messages_queue.is_some(),
+            UpdateKind::EditedMessage(_) => self.edited_messages_queue.is_some(),
+            UpdateKind::ChannelPost(_) => self.channel_posts_queue.is_some(),
+            UpdateKind::EditedChannelPost(_) => self.edited_channel_posts_queue.is_some(),
+            UpdateKind::InlineQuery(_) => self.inline_queries_queue.is_some(),
+            UpdateKind::ChosenInlineResult(_) => self.chosen_inline_results_queue.is_some(),
+            UpdateKind::CallbackQuery(_) => self.callback_queries_queue.is_some(),
+            UpdateKind::ShippingQuery(_) => self.shipping_queries_queue.is_some(),
+            UpdateKind::PreCheckoutQuery(_) => self.pre_checkout_queries_queue.is_some(),
+            UpdateKind::Poll(_) => self.polls_queue.is_some(),
+            UpdateKind::PollAnswer(_) => self.poll_answers_queue.is_some(),
+            UpdateKind::MyChatMember(_) => self.my_chat_members_queue.is_some(),
+            UpdateKind::ChatMember(_) => self.chat_members_queue.is_some(),
         }
+    }
 
-        let mut allowed = hint_handler_allowed_update(&self.messages_queue, AllowedUpdate::Message)
-            .chain(hint_handler_allowed_update(
-                &self.edited_messages_queue,
-                AllowedUpdate::EditedMessage,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.channel_posts_queue,
-                AllowedUpdate::ChannelPost,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.edited_channel_posts_queue,
-                AllowedUpdate::EditedChannelPost,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.inline_queries_queue,
-                AllowedUpdate::InlineQuery,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.chosen_inline_results_queue,
-                AllowedUpdate::ChosenInlineResult,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.callback_queries_queue,
-                AllowedUpdate::CallbackQuery,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.shipping_queries_queue,
-                AllowedUpdate::ShippingQuery,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.pre_checkout_queries_queue,
-                AllowedUpdate::PreCheckoutQuery,
-            ))
-            .chain(hint_handler_allowed_update(&self.polls_queue, AllowedUpdate::Poll))
-            .chain(hint_handler_allowed_update(&self.poll_answers_queue, AllowedUpdate::PollAnswer))
-            .chain(hint_handler_allowed_update(
-                &self.my_chat_members_queue,
-                AllowedUpdate::MyChatMember,
-            ))
-            .chain(hint_handler_allowed_update(
-                &self.chat_members_queue,
-                AllowedUpdate::ChatMember,
-            ));
+    fn hint_allowed_updates<E>(&self, listener: &mut impl UpdateListener<E>) {
+        let catch_all = self.updates_queue.is_some();
+        let hint_handler_allowed_update = |queue_is_some: bool, kind: AllowedUpdate| {
+            if queue_is_some || catch_all {
+                Some(kind)
+            } else {
+                None
+            }
+        };
+
+        let mut allowed =
+            hint_handler_allowed_update(self.messages_queue.is_some(), AllowedUpdate::Message)
+                .into_iter()
+                .chain(hint_handler_allowed_update(
+                    self.edited_messages_queue.is_some(),
+                    AllowedUpdate::EditedMessage,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.channel_posts_queue.is_some(),
+                    AllowedUpdate::ChannelPost,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.edited_channel_posts_queue.is_some(),
+                    AllowedUpdate::EditedChannelPost,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.inline_queries_queue.is_some(),
+                    AllowedUpdate::InlineQuery,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.chosen_inline_results_queue.is_some(),
+                    AllowedUpdate::ChosenInlineResult,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.callback_queries_queue.is_some(),
+                    AllowedUpdate::CallbackQuery,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.shipping_queries_queue.is_some(),
+                    AllowedUpdate::ShippingQuery,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.pre_checkout_queries_queue.is_some(),
+                    AllowedUpdate::PreCheckoutQuery,
+                ))
+                .chain(hint_handler_allowed_update(self.polls_queue.is_some(), AllowedUpdate::Poll))
+                .chain(hint_handler_allowed_update(
+                    self.poll_answers_queue.is_some(),
+                    AllowedUpdate::PollAnswer,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.my_chat_members_queue.is_some(),
+                    AllowedUpdate::MyChatMember,
+                ))
+                .chain(hint_handler_allowed_update(
+                    self.chat_members_queue.is_some(),
+                    AllowedUpdate::ChatMember,
+                ));
 
         listener.hint_allowed_updates(&mut allowed);
     }
@@ -522,6 +785,8 @@ where
         self.poll_answers_queue.take();
         self.my_chat_members_queue.take();
         self.chat_members_queue.take();
+        self.updates_queue.take();
+        self.unauthorized_updates_queue.take();
 
         // Wait untill all handlers finish
         self.running_handlers.by_ref().for_each(|_| async {}).await;
@@ -568,6 +833,9 @@ impl ShutdownToken {
 
 struct DispatcherState {
     inner: AtomicU8,
+    /// Notified when the state becomes `ShuttingDown`, so that the dispatching
+    /// loop doesn't wait for `shutdown_check_timeout` to notice it.
+    shutting_down: Notify,
 }
 
 impl DispatcherState {
@@ -593,7 +861,7 @@ impl DispatcherState {
 
 impl Default for DispatcherState {
     fn default() -> Self {
-        Self { inner: AtomicU8::new(ShutdownState::Idle as _) }
+        Self { inner: AtomicU8::new(ShutdownState::Idle as _), shutting_down: Notify::new() }
     }
 }
 
@@ -642,20 +910,92 @@ fn shutdown_inner(
     let res = state.compare_exchange(Running, ShuttingDown);
 
     match res {
-        Ok(_) => Ok(()),
+        Ok(_) => {
+            // `notify_one` stores a permit if the dispatching loop isn't
+            // waiting at the moment, so the notification isn't lost.
+            state.shutting_down.notify_one();
+            Ok(())
+        }
         Err(ShuttingDown) => Err(Ok(AlreadyShuttingDown)),
         Err(Idle) => Err(Err(IdleShutdownError)),
         Err(Running) => unreachable!(),
     }
 }
 
-fn send<'a, R, Upd>(requester: &'a R, tx: &'a Tx<R, Upd>, update: Upd, variant: &'static str)
-where
+async fn open_record(path: &Path) -> Option<File> {
+    let file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await;
+
+    match file {
+        Ok(file) => Some(file),
+        Err(error) => {
+            log::error!("Failed to open {} to record updates: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+async fn record_update(file: &mut File, update: &Update) {
+    let update = match update_to_json(update) {
+        Ok(update) => update,
+        Err(error) => {
+            log::error!("Failed to serialize an update to record it: {}", error);
+            return;
+        }
+    };
+
+    let mut line = update.to_string().into_bytes();
+    line.push(b'\n');
+
+    let res = async {
+        file.write_all(&line).await?;
+        file.flush().await
+    };
+    if let Err(error) = res.await {
+        log::error!("Failed to record an update: {}", error);
+    }
+}
+
+/// Serializes `update` the way Telegram does.
+///
+/// teloxide-core serializes the type of a private chat as `null`, but
+/// deserializes only `"private"`, so with plain `serde_json::to_value` updates
+/// from private chats couldn't be read back.
+pub(crate) fn update_to_json(update: &Update) -> serde_json::Result<serde_json::Value> {
+    let mut update = serde_json::to_value(update)?;
+    restore_private_chat_types(&mut update);
+    Ok(update)
+}
+
+fn restore_private_chat_types(value: &mut serde_json::Value) {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(object) => {
+            if let Some(type_ @ Value::Null) = object.get_mut("type") {
+                *type_ = Value::from("private");
+            }
+            object.values_mut().for_each(restore_private_chat_types);
+        }
+        Value::Array(array) => array.iter_mut().for_each(restore_private_chat_types),
+        _ => {}
+    }
+}
+
+fn send<'a, R, Upd>(
+    requester: &'a R,
+    tx: &'a Tx<R, Upd>,
+    update: Upd,
+    permit: Option<Arc<OwnedSemaphorePermit>>,
+    variant: &'static str,
+) where
     Upd: Debug,
     R: Requester + Clone,
 {
     if let Some(tx) = tx {
-        if let Err(error) = tx.send(UpdateWithCx { requester: requester.clone(), update }) {
+        let mut cx = UpdateWithCx::new(requester.clone(), update);
+        cx.permit = permit;
+
+        if let Err(error) = tx.send(cx) {
             log::error!(
                 "The RX part of the {} channel is closed, but an update is received.\nError:{}\n",
                 variant,
@@ -664,3 +1004,391 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{convert::Infallible, sync::Mutex};
+
+    use teloxide_core::Bot;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use crate::{
+        dispatching::{update_listeners::StatefulListener, DispatcherHandlerRx},
+        mock::{callback_query_update, message_update, updates_json, user},
+        utils::MessageBuilder,
+    };
+
+    fn update(json: serde_json::Value) -> Update {
+        serde_json::from_value(json).unwrap()
+    }
+
+    async fn dispatch(dispatcher: &mut Dispatcher<Bot>, updates: Vec<Update>) {
+        let stream = futures::stream::iter(updates.into_iter().map(Ok::<_, Infallible>));
+        let listener = StatefulListener::from_stream_without_graceful_shutdown(stream);
+
+        dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::new()).await;
+    }
+
+    #[tokio::test]
+    async fn updates_handler_receives_all_kinds() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here")).updates_handler({
+            let received = Arc::clone(&received);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    received.lock().unwrap().push(cx.update.id);
+                    async {}
+                })
+            }
+        });
+
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello"), callback_query_update(2)]).await;
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn specific_handler_takes_precedence() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let messages = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .messages_handler({
+                let messages = Arc::clone(&messages);
+                move |rx: DispatcherHandlerRx<Bot, Message>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        messages.lock().unwrap().push(cx.update.id);
+                        async {}
+                    })
+                }
+            })
+            .updates_handler({
+                let received = Arc::clone(&received);
+                move |rx: DispatcherHandlerRx<Bot, Update>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        received.lock().unwrap().push(cx.update.id);
+                        async {}
+                    })
+                }
+            });
+
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello"), callback_query_update(2)]).await;
+
+        assert_eq!(*messages.lock().unwrap(), vec![1]);
+        assert_eq!(*received.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn inline_queries_are_routed() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .inline_queries_handler({
+                let queries = Arc::clone(&queries);
+                move |rx: DispatcherHandlerRx<Bot, InlineQuery>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        queries
+                            .lock()
+                            .unwrap()
+                            .push((cx.update.query, cx.update.location.is_some()));
+                        async {}
+                    })
+                }
+            });
+
+        let inline_query = update(serde_json::json!({
+            "update_id": 3,
+            "inline_query": {
+                "id": "1",
+                "from": user(1),
+                "query": "cats",
+                "offset": "",
+                "location": { "longitude": 37.62, "latitude": 55.75 }
+            }
+        }));
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello"), inline_query]).await;
+
+        assert_eq!(*queries.lock().unwrap(), vec![("cats".to_owned(), true)]);
+    }
+
+    #[tokio::test]
+    async fn chosen_inline_results_are_routed() {
+        let results = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .chosen_inline_results_handler({
+                let results = Arc::clone(&results);
+                move |rx: DispatcherHandlerRx<Bot, ChosenInlineResult>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        results.lock().unwrap().push((cx.update.result_id, cx.update.query));
+                        async {}
+                    })
+                }
+            });
+
+        let chosen_inline_result = update(serde_json::json!({
+            "update_id": 4,
+            "chosen_inline_result": {
+                "result_id": "42",
+                "from": user(1),
+                "inline_message_id": "1",
+                "query": "cats"
+            }
+        }));
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello"), chosen_inline_result]).await;
+
+        assert_eq!(*results.lock().unwrap(), vec![("42".to_owned(), "cats".to_owned())]);
+    }
+
+    #[tokio::test]
+    async fn handlers_get_dispatcher_requester() {
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("TOKEN")).updates_handler({
+            let tokens = Arc::clone(&tokens);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    tokens.lock().unwrap().push(cx.requester.token().to_owned());
+                    async {}
+                })
+            }
+        });
+        assert_eq!(dispatcher.requester().token(), "TOKEN");
+
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello")]).await;
+
+        assert_eq!(*tokens.lock().unwrap(), vec!["TOKEN".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn handlers_share_dispatcher_requester() {
+        let tokens = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("TOKEN")).updates_handler({
+            let tokens = Arc::clone(&tokens);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    tokens.lock().unwrap().push(cx.requester.token().as_ptr() as usize);
+                    async {}
+                })
+            }
+        });
+
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello")]).await;
+
+        // Clones of a bot share the same `Arc<str>` token (and the same client), so handlers get
+        // the very requester of the dispatcher rather than an equal one.
+        let token = dispatcher.requester().token().as_ptr() as usize;
+        assert_ne!(token, Bot::new("TOKEN").token().as_ptr() as usize);
+        assert_eq!(*tokens.lock().unwrap(), vec![token]);
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_doesnt_stop_dispatching() {
+        use crate::dispatching::FnHandler;
+
+        let handled = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here")).messages_handler(
+            FnHandler::new({
+                let handled = Arc::clone(&handled);
+                move |cx: UpdateWithCx<Bot, Message>| {
+                    let handled = Arc::clone(&handled);
+                    async move {
+                        if cx.update.text() == Some("panic") {
+                            panic!("Oh no");
+                        }
+
+                        handled.lock().unwrap().push(cx.update.id);
+                    }
+                }
+            })
+            .per_chat_lock(),
+        );
+
+        let messages = vec![message_update(0, "panic"), message_update(1, "Hello")];
+        dispatch(&mut dispatcher, messages).await;
+
+        assert_eq!(*handled.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn max_concurrent_updates_is_respected() {
+        use std::sync::atomic::AtomicUsize;
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .max_concurrent_updates(2)
+            .updates_handler({
+                let active = Arc::clone(&active);
+                let max = Arc::clone(&max);
+                move |rx: DispatcherHandlerRx<Bot, Update>| {
+                    UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx| {
+                        let active = Arc::clone(&active);
+                        let max = Arc::clone(&max);
+                        async move {
+                            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                            max.fetch_max(now, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            active.fetch_sub(1, Ordering::SeqCst);
+
+                            // The update is handled until it's dropped.
+                            drop(cx);
+                        }
+                    })
+                }
+            });
+
+        let updates = (0..6).map(callback_query_update).collect();
+        dispatch(&mut dispatcher, updates).await;
+
+        assert_eq!(max.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn restrict_to_chats_drops_other_chats() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let unauthorized = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .restrict_to_chats(vec![1])
+            .messages_handler({
+                let messages = Arc::clone(&messages);
+                move |rx: DispatcherHandlerRx<Bot, Message>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        messages.lock().unwrap().push(cx.update.chat.id);
+                        async {}
+                    })
+                }
+            })
+            .unauthorized_updates_handler({
+                let unauthorized = Arc::clone(&unauthorized);
+                move |rx: DispatcherHandlerRx<Bot, Update>| {
+                    UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                        unauthorized.lock().unwrap().push(cx.update.chat_id());
+                        async {}
+                    })
+                }
+            });
+
+        let message_from = |chat_id: i64| {
+            let message = MessageBuilder::new().text("Hello").from_user(chat_id).chat(chat_id);
+            Update { id: chat_id as i32, kind: UpdateKind::Message(message.build()) }
+        };
+        dispatch(&mut dispatcher, vec![message_from(1), message_from(2)]).await;
+
+        assert_eq!(*messages.lock().unwrap(), vec![1]);
+        assert_eq!(*unauthorized.lock().unwrap(), vec![Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn pending_updates_arent_dispatched() {
+        use crate::mock::MockApi;
+
+        // Updates #1-#3 are pending when the bot starts; #4 arrives after they are
+        // confirmed.
+        let confirmed = Mutex::new(0);
+        let api = MockApi::start(move |method, params| {
+            if method == "GetWebhookInfo" {
+                return serde_json::json!({ "url": "", "has_custom_certificate": false, "pending_update_count": 0 });
+            }
+
+            let mut confirmed = confirmed.lock().unwrap();
+            let last = if *confirmed >= 3 { 4 } else { 3 };
+
+            // See `offset` in https://core.telegram.org/bots/api#getupdates.
+            let offset = params["offset"].as_i64().unwrap_or(0);
+            if offset > 0 {
+                *confirmed = (*confirmed).max(offset - 1);
+            } else if offset < 0 {
+                *confirmed = (*confirmed).max(last + offset);
+            }
+
+            let limit = params["limit"].as_u64().unwrap_or(100) as usize;
+            let updates: Vec<_> = (*confirmed + 1..=last)
+                .take(limit)
+                .map(|id| message_update(id as i32, "Hello"))
+                .collect();
+            updates_json(&updates)
+        })
+        .await;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(api.bot()).drop_pending_updates();
+        let shutdown_token = dispatcher.shutdown_token();
+        dispatcher = dispatcher.messages_handler({
+            let received = Arc::clone(&received);
+            move |rx: DispatcherHandlerRx<Bot, Message>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    received.lock().unwrap().push(cx.update.id);
+                    let _ = shutdown_token.shutdown();
+                    async {}
+                })
+            }
+        });
+
+        dispatcher.dispatch().await;
+
+        assert_eq!(*received.lock().unwrap(), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn pending_updates_arent_dropped_for_custom_listeners() {
+        use crate::mock::MockApi;
+
+        let api = MockApi::start(|_, _| serde_json::json!([])).await;
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(api.bot()).drop_pending_updates().messages_handler({
+            let received = Arc::clone(&received);
+            move |rx: DispatcherHandlerRx<Bot, Message>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    received.lock().unwrap().push(cx.update.id);
+                    async {}
+                })
+            }
+        });
+        let stream = futures::stream::iter(vec![Ok::<_, Infallible>(message_update(1, "Hello"))]);
+        let listener = StatefulListener::from_stream_without_graceful_shutdown(stream);
+        dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::new()).await;
+
+        assert_eq!(api.requests(), vec![]);
+        assert_eq!(*received.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn recorded_updates_are_replayed() {
+        let path = std::env::temp_dir()
+            .join(format!("teloxide_recorded_updates_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"))
+            .record_updates(&path)
+            .updates_handler(|rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(|_| async {})
+            });
+        dispatch(&mut dispatcher, vec![message_update(1, "Hello"), callback_query_update(2)]).await;
+
+        let replayed = Arc::new(Mutex::new(Vec::new()));
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here")).updates_handler({
+            let replayed = Arc::clone(&replayed);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    replayed.lock().unwrap().push(cx.update.id);
+                    async {}
+                })
+            }
+        });
+        let listener = update_listeners::from_file(&path).await.unwrap();
+        dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::new()).await;
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(*replayed.lock().unwrap(), vec![1, 2]);
+    }
+}
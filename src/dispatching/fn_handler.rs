@@ -0,0 +1,638 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use futures::{future::BoxFuture, StreamExt};
+use teloxide_core::{
+    requests::{Request, Requester},
+    types::CallbackQuery,
+};
+use thiserror::Error;
+use tokio::{sync::OwnedMutexGuard, task::JoinError};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::{
+    dispatching::{
+        dialogue::GetChatId, DispatcherHandler, DispatcherHandlerRx, IntoHandlerResult,
+        UpdateWithCx,
+    },
+    error_handlers::{ErrorHandler, LoggingErrorHandler},
+};
+
+/// A [`DispatcherHandler`] created from a function handling a single update.
+///
+/// Updates are handled concurrently and every handler invocation is spawned as
+/// a separate task, so a panicking handler doesn't stop handling of other
+/// updates. The handler can return anything implementing
+/// [`IntoHandlerResult`], e.g. `()` or `Result<(), E>`; errors and panics are
+/// passed into an error handler (see [`FnHandler::error_handler`]), which logs
+/// them by default.
+///
+/// Optionally, updates from the same chat can be serialized (see
+/// [`FnHandler::per_chat_lock`]) and callback queries can be answered
+/// automatically (see [`FnHandler::auto_answer_callback_queries`]). To limit
+/// the number of concurrently handled updates, see
+/// [`Dispatcher::max_concurrent_updates`].
+///
+/// ## Example
+/// ```no_run
+/// use teloxide::{dispatching::FnHandler, prelude::*};
+///
+/// # async fn run() {
+/// let bot = Bot::from_env().auto_send();
+///
+/// Dispatcher::new(bot)
+///     .messages_handler(
+///         FnHandler::new(|message: UpdateWithCx<AutoSend<Bot>, Message>| async move {
+///             message.answer("pong").await?;
+///             respond(())
+///         })
+///         .per_chat_lock(),
+///     )
+///     .dispatch()
+///     .await;
+/// # }
+/// ```
+///
+/// [`DispatcherHandler`]: crate::dispatching::DispatcherHandler
+/// [`IntoHandlerResult`]: crate::dispatching::IntoHandlerResult
+/// [`Dispatcher::max_concurrent_updates`]: crate::dispatching::Dispatcher::max_concurrent_updates
+pub struct FnHandler<R, Upd, F, Eh = LoggingErrorHandler> {
+    handler: Arc<F>,
+    chat_lock: Option<ChatLock<Upd>>,
+    after_handled: Option<AfterHandled<R, Upd>>,
+    error_handler: Arc<Eh>,
+    _phantom: PhantomData<fn(UpdateWithCx<R, Upd>)>,
+}
+
+impl<R, Upd, F> FnHandler<R, Upd, F> {
+    /// Creates a handler from the specified function.
+    #[must_use]
+    pub fn new<Fut>(handler: F) -> Self
+    where
+        F: Fn(UpdateWithCx<R, Upd>) -> Fut,
+    {
+        Self {
+            handler: Arc::new(handler),
+            chat_lock: None,
+            after_handled: None,
+            error_handler: LoggingErrorHandler::with_custom_text("An error from a handler"),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<R, Upd, F, Eh> FnHandler<R, Upd, F, Eh> {
+    /// Sets a handler of errors returned from the function and of its panics,
+    /// which are passed as [`HandlerError`]s.
+    ///
+    /// By default, they are logged.
+    #[must_use]
+    pub fn error_handler<NewEh>(self, error_handler: Arc<NewEh>) -> FnHandler<R, Upd, F, NewEh> {
+        let Self { handler, chat_lock, after_handled, .. } = self;
+        FnHandler { handler, chat_lock, after_handled, error_handler, _phantom: PhantomData }
+    }
+
+    /// Handles updates from the same chat sequentially.
+    ///
+    /// A handler invocation for an update waits until all the invocations for
+    /// previous updates from the same chat are finished (or panicked), while
+    /// updates from different chats are still handled concurrently.
+    #[must_use]
+    pub fn per_chat_lock(mut self) -> Self
+    where
+        Upd: GetChatId,
+    {
+        self.chat_lock = Some(ChatLock { locks: Arc::new(ChatLocks::new()), key: Upd::chat_id });
+        self
+    }
+}
+
+impl<R, F, Eh> FnHandler<R, CallbackQuery, F, Eh> {
+    /// Answers every callback query after the handler has finished.
+    ///
+    /// Until a callback query is answered, Telegram clients show a progress
+    /// bar on the pressed button. With this option, an empty
+    /// [`AnswerCallbackQuery`] is sent after each handler invocation (even if
+    /// it has panicked), so that the progress bar is always cleared.
+    ///
+    /// The query isn't answered once again if the handler has already answered
+    /// it via [`UpdateWithCx::answer`] (or another `answer_*` method). Answers
+    /// sent directly through the requester aren't tracked: since Telegram
+    /// doesn't allow to answer a query twice, the repeated answer fails; such
+    /// failures are logged at the debug level.
+    ///
+    /// [`AnswerCallbackQuery`]: crate::payloads::AnswerCallbackQuery
+    #[must_use]
+    pub fn auto_answer_callback_queries(mut self) -> Self
+    where
+        R: Requester + Clone + Send + 'static,
+        <R as Requester>::AnswerCallbackQuery: Send,
+    {
+        self.after_handled = Some(answer_callback_query::<R>);
+        self
+    }
+}
+
+impl<R, Upd, F, Fut, Eh> DispatcherHandler<R, Upd> for FnHandler<R, Upd, F, Eh>
+where
+    F: Fn(UpdateWithCx<R, Upd>) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoHandlerResult + Send,
+    <Fut::Output as IntoHandlerResult>::Error: Debug + Send + 'static,
+    Eh: ErrorHandler<HandlerError<<Fut::Output as IntoHandlerResult>::Error>>
+        + Send
+        + Sync
+        + 'static,
+    R: Send + 'static,
+    Upd: Send + 'static,
+{
+    fn handle(self, updates: DispatcherHandlerRx<R, Upd>) -> BoxFuture<'static, ()>
+    where
+        UpdateWithCx<R, Upd>: Send + 'static,
+    {
+        let Self { handler, chat_lock, after_handled, error_handler, .. } = self;
+
+        let updates = UnboundedReceiverStream::new(updates);
+        Box::pin(updates.for_each_concurrent(None, move |mut cx| {
+            let handler = Arc::clone(&handler);
+            let error_handler = Arc::clone(&error_handler);
+            let chat_lock = chat_lock.clone();
+            let after_handled = after_handled.map(|after_handled| after_handled(&mut cx));
+
+            async move {
+                // The guard is dropped after the spawned task has finished, even if it has
+                // panicked.
+                let _guard = match chat_lock {
+                    Some(ChatLock { locks, key }) => Some(locks.lock(key(&cx.update)).await),
+                    None => None,
+                };
+
+                let error = match tokio::spawn(async move { handler(cx).await }).await {
+                    Ok(result) => result.into_handler_result().err().map(HandlerError::Returned),
+                    Err(error) => Some(HandlerError::Panic(panic_message(error))),
+                };
+                if let Some(error) = error {
+                    error_handler.handle_error(error).await;
+                }
+
+                if let Some(after_handled) = after_handled {
+                    after_handled.await;
+                }
+            }
+        }))
+    }
+}
+
+/// An error of a [`FnHandler`] function.
+#[derive(Debug, Error)]
+pub enum HandlerError<E> {
+    /// The function has returned an error.
+    #[error("{0}")]
+    Returned(E),
+
+    /// The function has panicked with the message.
+    #[error("a handler has panicked: {0}")]
+    Panic(String),
+}
+
+/// Extracts the message of a panic, if the task has panicked with a string
+/// (e.g. via `panic!` or `expect`).
+fn panic_message(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+
+    let payload = error.into_panic();
+    match payload.downcast_ref::<&str>() {
+        Some(message) => (*message).to_owned(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "<a non-string payload>".to_owned(),
+        },
+    }
+}
+
+/// Creates a future which is run after an update is handled.
+type AfterHandled<R, Upd> = fn(&mut UpdateWithCx<R, Upd>) -> BoxFuture<'static, ()>;
+
+fn answer_callback_query<R>(cx: &mut UpdateWithCx<R, CallbackQuery>) -> BoxFuture<'static, ()>
+where
+    R: Requester + Clone + Send + 'static,
+    <R as Requester>::AnswerCallbackQuery: Send,
+{
+    let requester = cx.requester.clone();
+    let id = cx.update.id.clone();
+    let answered = Arc::clone(cx.answered.get_or_insert_with(Default::default));
+
+    Box::pin(async move {
+        if answered.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Err(error) = requester.answer_callback_query(id.clone()).send().await {
+            log::debug!("Failed to automatically answer the callback query {}: {:?}", id, error);
+        }
+    })
+}
+
+struct ChatLock<Upd> {
+    locks: Arc<ChatLocks>,
+    key: fn(&Upd) -> i64,
+}
+
+impl<Upd> Clone for ChatLock<Upd> {
+    fn clone(&self) -> Self {
+        Self { locks: Arc::clone(&self.locks), key: self.key }
+    }
+}
+
+const CHAT_LOCKS_SHARDS: usize = 32;
+
+/// Asynchronous mutexes indexed by chat IDs.
+///
+/// The map is split into shards, so that locking of different chats rarely
+/// contends on the same synchronous mutex. A mutex is removed from the map
+/// as soon as nobody holds or awaits it.
+struct ChatLocks {
+    shards: Vec<Mutex<HashMap<i64, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl ChatLocks {
+    fn new() -> Self {
+        Self { shards: (0..CHAT_LOCKS_SHARDS).map(|_| Mutex::new(HashMap::new())).collect() }
+    }
+
+    async fn lock(self: Arc<Self>, chat_id: i64) -> ChatLockGuard {
+        let mutex = {
+            let mut shard = self.shard(chat_id).lock().expect("A shard is never poisoned");
+            Arc::clone(shard.entry(chat_id).or_default())
+        };
+
+        let guard = mutex.lock_owned().await;
+        ChatLockGuard { locks: self, chat_id, guard: Some(guard) }
+    }
+
+    fn shard(&self, chat_id: i64) -> &Mutex<HashMap<i64, Arc<tokio::sync::Mutex<()>>>> {
+        &self.shards[chat_id.rem_euclid(CHAT_LOCKS_SHARDS as i64) as usize]
+    }
+}
+
+struct ChatLockGuard {
+    locks: Arc<ChatLocks>,
+    chat_id: i64,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for ChatLockGuard {
+    fn drop(&mut self) {
+        // Release the mutex before checking whether it is still used by someone.
+        self.guard.take();
+
+        let mut shard = match self.locks.shard(self.chat_id).lock() {
+            Ok(shard) => shard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Some(mutex) = shard.get(&self.chat_id) {
+            if Arc::strong_count(mutex) == 1 {
+                shard.remove(&self.chat_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use teloxide_core::Bot;
+    use tokio::sync::mpsc;
+
+    use crate::mock::{callback_query, MockApi};
+
+    #[derive(Debug)]
+    struct MyUpdate {
+        chat_id: i64,
+    }
+
+    impl GetChatId for MyUpdate {
+        fn chat_id(&self) -> i64 {
+            self.chat_id
+        }
+    }
+
+    async fn max_concurrency(updates: Vec<MyUpdate>) -> usize {
+        let active = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+
+        let handler = {
+            let active = Arc::clone(&active);
+            let max = Arc::clone(&max);
+
+            FnHandler::new(move |_: UpdateWithCx<Bot, MyUpdate>| {
+                let active = Arc::clone(&active);
+                let max = Arc::clone(&max);
+
+                async move {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+
+                    Ok::<_, Infallible>(())
+                }
+            })
+            .per_chat_lock()
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        for update in updates {
+            tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        }
+        drop(tx);
+
+        handler.handle(rx).await;
+
+        max.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn same_chat_is_handled_sequentially() {
+        let updates = vec![MyUpdate { chat_id: 1 }, MyUpdate { chat_id: 1 }];
+        assert_eq!(max_concurrency(updates).await, 1);
+    }
+
+    #[tokio::test]
+    async fn different_chats_are_handled_concurrently() {
+        let updates = vec![MyUpdate { chat_id: 1 }, MyUpdate { chat_id: 2 }];
+        assert_eq!(max_concurrency(updates).await, 2);
+    }
+
+    #[tokio::test]
+    async fn errors_and_panics_are_passed_to_error_handler() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = FnHandler::new(|cx: UpdateWithCx<Bot, MyUpdate>| async move {
+            if cx.update.chat_id == 1 {
+                panic!("Oh no");
+            }
+
+            Err::<(), _>("Bad chat")
+        })
+        .error_handler(Arc::new({
+            let errors = Arc::clone(&errors);
+            move |error: HandlerError<&'static str>| {
+                errors.lock().unwrap().push(error.to_string());
+                async {}
+            }
+        }));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        for chat_id in 1..=2 {
+            let update = MyUpdate { chat_id };
+            tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        }
+        drop(tx);
+
+        handler.handle(rx).await;
+
+        let mut errors = errors.lock().unwrap().clone();
+        errors.sort();
+        assert_eq!(errors, vec!["Bad chat", "a handler has panicked: Oh no"]);
+    }
+
+    #[tokio::test]
+    async fn lock_is_released_after_panic() {
+        let handler = FnHandler::new(|cx: UpdateWithCx<Bot, MyUpdate>| async move {
+            if cx.update.chat_id == 1 {
+                panic!("Oh no");
+            }
+
+            Ok::<_, Infallible>(())
+        })
+        .per_chat_lock();
+        let locks = Arc::clone(&handler.chat_lock.as_ref().unwrap().locks);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        for _ in 0..2 {
+            let update = MyUpdate { chat_id: 1 };
+            tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        }
+        drop(tx);
+
+        handler.handle(rx).await;
+
+        assert!(locks.shards.iter().all(|shard| shard.lock().unwrap().is_empty()));
+    }
+
+    #[tokio::test]
+    async fn runs_after_handled_when_handler_has_finished() {
+        static STEP: AtomicUsize = AtomicUsize::new(0);
+
+        fn after_handled(_: &mut UpdateWithCx<Bot, MyUpdate>) -> BoxFuture<'static, ()> {
+            Box::pin(async {
+                assert_eq!(STEP.fetch_add(1, Ordering::SeqCst), 1);
+            })
+        }
+
+        let mut handler = FnHandler::new(|_: UpdateWithCx<Bot, MyUpdate>| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(STEP.fetch_add(1, Ordering::SeqCst), 0);
+
+            Ok::<_, Infallible>(())
+        });
+        handler.after_handled = Some(after_handled);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let update = MyUpdate { chat_id: 1 };
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        drop(tx);
+
+        handler.handle(rx).await;
+
+        assert_eq!(STEP.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn auto_answer_callback_queries_is_set() {
+        let handler =
+            FnHandler::new(|_: UpdateWithCx<Bot, CallbackQuery>| async { Ok::<_, Infallible>(()) })
+                .auto_answer_callback_queries();
+
+        assert!(handler.after_handled.is_some());
+    }
+
+    async fn handle_callback_query<F, Fut>(api: MockApi, f: F) -> Vec<(String, serde_json::Value)>
+    where
+        F: Fn(UpdateWithCx<Bot, CallbackQuery>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: IntoHandlerResult + Send,
+        <Fut::Output as IntoHandlerResult>::Error: Debug + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(UpdateWithCx::new(api.bot(), callback_query(1))).unwrap();
+        drop(tx);
+
+        FnHandler::new(f).auto_answer_callback_queries().handle(rx).await;
+
+        api.requests()
+    }
+
+    #[tokio::test]
+    async fn unanswered_callback_query_is_answered() {
+        let api = MockApi::start(|_, _| serde_json::json!(true)).await;
+        let requests = handle_callback_query(api, |_| async {}).await;
+
+        let answer = serde_json::json!({ "callback_query_id": "1" });
+        assert_eq!(requests, vec![("AnswerCallbackQuery".to_owned(), answer)]);
+    }
+
+    #[tokio::test]
+    async fn answered_callback_query_isnt_answered_again() {
+        let api = MockApi::start(|_, _| serde_json::json!(true)).await;
+        let requests = handle_callback_query(api, |cx| async move {
+            cx.answer_with_text("Done", false).send().await.map(drop)
+        })
+        .await;
+
+        let answer =
+            serde_json::json!({ "callback_query_id": "1", "text": "Done", "show_alert": false });
+        assert_eq!(requests, vec![("AnswerCallbackQuery".to_owned(), answer)]);
+    }
+
+    #[tokio::test]
+    async fn failed_answer_is_retried() {
+        // The first answer fails, since `True` is expected in response.
+        let answers = AtomicUsize::new(0);
+        let api = MockApi::start(move |_, _| match answers.fetch_add(1, Ordering::SeqCst) {
+            0 => serde_json::json!("not true"),
+            _ => serde_json::json!(true),
+        })
+        .await;
+        let requests = handle_callback_query(api, |cx| async move {
+            cx.answer_with_text("Done", false).send().await.map(drop)
+        })
+        .await;
+
+        let answer =
+            serde_json::json!({ "callback_query_id": "1", "text": "Done", "show_alert": false });
+        let auto_answer = serde_json::json!({ "callback_query_id": "1" });
+        assert_eq!(
+            requests,
+            vec![
+                ("AnswerCallbackQuery".to_owned(), answer),
+                ("AnswerCallbackQuery".to_owned(), auto_answer)
+            ]
+        );
+    }
+
+    async fn handle_one<F, Fut>(f: F)
+    where
+        F: Fn(UpdateWithCx<Bot, MyUpdate>) -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: IntoHandlerResult + Send,
+        <Fut::Output as IntoHandlerResult>::Error: Debug + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let update = MyUpdate { chat_id: 1 };
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        drop(tx);
+
+        FnHandler::new(f).handle(rx).await;
+    }
+
+    #[tokio::test]
+    async fn handler_can_return_unit() {
+        static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+        handle_one(|_| async {
+            HANDLED.fetch_add(1, Ordering::SeqCst);
+        })
+        .await;
+
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn handler_can_return_boxed_error() {
+        static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+        handle_one(|_| async {
+            HANDLED.fetch_add(1, Ordering::SeqCst);
+            Err::<(), Box<dyn std::error::Error + Send + Sync>>("Oh no".into())
+        })
+        .await;
+
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn handler_can_return_transition_out() {
+        use crate::dispatching::dialogue::{DialogueStage, TransitionOut};
+
+        static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+        handle_one(|_| async {
+            HANDLED.fetch_add(1, Ordering::SeqCst);
+            let out: TransitionOut<()> = Ok(DialogueStage::Next(()));
+            out
+        })
+        .await;
+
+        assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn handler_can_return_error_not_implementing_std_error() {
+        // Like `anyhow::Error`, which doesn't implement `std::error::Error`.
+        #[derive(Debug)]
+        struct AnyError(&'static str);
+
+        impl std::fmt::Display for AnyError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = FnHandler::new(|_: UpdateWithCx<Bot, MyUpdate>| async {
+            Err::<(), _>(AnyError("Oh no"))
+        })
+        .error_handler(Arc::new({
+            let errors = Arc::clone(&errors);
+            move |error: HandlerError<AnyError>| {
+                errors.lock().unwrap().push(error.to_string());
+                async {}
+            }
+        }));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let update = MyUpdate { chat_id: 1 };
+        tx.send(UpdateWithCx::new(Bot::new("Doesn't matter here"), update)).unwrap();
+        drop(tx);
+
+        handler.handle(rx).await;
+
+        assert_eq!(*errors.lock().unwrap(), vec!["Oh no"]);
+    }
+
+    #[tokio::test]
+    async fn panic_message_is_extracted() {
+        let error = tokio::spawn(async { panic!("Oh no") }).await.unwrap_err();
+        assert_eq!(panic_message(error), "Oh no");
+
+        let error = tokio::spawn(async { panic!("Oh no: {}", 42) }).await.unwrap_err();
+        assert_eq!(panic_message(error), "Oh no: 42");
+    }
+}
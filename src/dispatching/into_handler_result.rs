@@ -0,0 +1,47 @@
+use std::convert::Infallible;
+
+use crate::dispatching::dialogue::DialogueStage;
+
+/// A value which can be returned from a handler, such as [`FnHandler`].
+///
+/// This allows handlers to return `()` if they cannot fail, and a [`Result`]
+/// with an arbitrary error type (e.g. [`RequestError`] or `anyhow::Error`)
+/// otherwise. Dialogue transitions returning [`TransitionOut`] can be used as
+/// handlers too.
+///
+/// [`FnHandler`]: crate::dispatching::FnHandler
+/// [`RequestError`]: crate::RequestError
+/// [`TransitionOut`]: crate::dispatching::dialogue::TransitionOut
+pub trait IntoHandlerResult {
+    /// The error returned from a handler.
+    type Error;
+
+    /// Converts the value into a result of a handler.
+    fn into_handler_result(self) -> Result<(), Self::Error>;
+}
+
+impl IntoHandlerResult for () {
+    type Error = Infallible;
+
+    fn into_handler_result(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<E> IntoHandlerResult for Result<(), E> {
+    type Error = E;
+
+    fn into_handler_result(self) -> Result<(), Self::Error> {
+        self
+    }
+}
+
+/// The next dialogue stage is ignored, so that a transition can be reused as a
+/// handler which doesn't store dialogues.
+impl<D, E> IntoHandlerResult for Result<DialogueStage<D>, E> {
+    type Error = E;
+
+    fn into_handler_result(self) -> Result<(), Self::Error> {
+        self.map(drop)
+    }
+}
@@ -32,9 +32,13 @@
 //!
 //! Since they implement [`DispatcherHandler`] too.
 //!
+//! To serve several bots from one process, put their dispatchers into a
+//! [`MultiDispatcher`].
+//!
 //! [See the examples](https://github.com/teloxide/teloxide/tree/master/examples).
 //!
 //! [`Dispatcher`]: crate::dispatching::Dispatcher
+//! [`MultiDispatcher`]: crate::dispatching::MultiDispatcher
 //! [all the update kinds]: crate::types::UpdateKind
 //! [`Update`]: crate::types::Update
 //! [`ErrorHandler`]: crate::dispatching::ErrorHandler
@@ -54,13 +58,23 @@ pub(crate) mod repls;
 mod dispatcher;
 mod dispatcher_handler;
 mod dispatcher_handler_rx_ext;
+mod fn_handler;
+mod into_handler_result;
+mod multi_dispatcher;
+mod update_ext;
 mod update_with_cx;
 
 pub use dispatcher::{Dispatcher, IdleShutdownError, ShutdownToken};
+#[cfg(test)]
+pub(crate) use dispatcher::update_to_json;
 pub use dispatcher_handler::DispatcherHandler;
 pub use dispatcher_handler_rx_ext::DispatcherHandlerRxExt;
+pub use fn_handler::{FnHandler, HandlerError};
+pub use into_handler_result::IntoHandlerResult;
+pub use multi_dispatcher::MultiDispatcher;
+pub use update_ext::UpdateExt;
 use tokio::sync::mpsc::UnboundedReceiver;
-pub use update_with_cx::{UpdateWithCx, UpdateWithCxRequesterType};
+pub use update_with_cx::{CallbackQueryAnswer, UpdateWithCx, UpdateWithCxRequesterType};
 
 /// A type of a stream, consumed by [`Dispatcher`]'s handlers.
 ///
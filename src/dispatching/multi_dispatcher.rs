@@ -0,0 +1,189 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash, sync::Arc};
+
+use futures::future;
+use teloxide_core::requests::Requester;
+
+use crate::{
+    dispatching::{update_listeners::UpdateListener, Dispatcher},
+    error_handlers::ErrorHandler,
+};
+
+/// A set of [`Dispatcher`]s, each serving its own bot, running in one
+/// process.
+///
+/// Dispatchers are identified by keys of type `K` (e.g. a customer id) and
+/// dispatch concurrently. Each of them has its own handlers, so a handler of
+/// one bot never receives updates of another bot. Handlers run in their own
+/// tasks, so a panic or an error in a handler of one bot doesn't affect the
+/// others.
+///
+/// ## Examples
+/// ```no_run
+/// use teloxide::{dispatching::MultiDispatcher, prelude::*};
+/// use tokio_stream::wrappers::UnboundedReceiverStream;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let dispatcher = |token: &str| {
+///     Dispatcher::new(Bot::new(token).auto_send()).messages_handler(
+///         |rx: DispatcherHandlerRx<AutoSend<Bot>, Message>| {
+///             UnboundedReceiverStream::new(rx).for_each_concurrent(None, |message| async move {
+///                 message.answer("pong").await.log_on_error().await;
+///             })
+///         },
+///     )
+/// };
+///
+/// MultiDispatcher::new()
+///     .add("first customer", dispatcher("FIRST_TOKEN"))
+///     .add("second customer", dispatcher("SECOND_TOKEN"))
+///     .dispatch()
+///     .await;
+/// # }
+/// ```
+pub struct MultiDispatcher<K, R> {
+    dispatchers: HashMap<K, Dispatcher<R>>,
+}
+
+impl<K, R> MultiDispatcher<K, R>
+where
+    K: Eq + Hash,
+    R: Send + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self { dispatchers: HashMap::new() }
+    }
+
+    /// Adds a `dispatcher` identified by `key`.
+    ///
+    /// A dispatcher previously added with the same `key` is replaced.
+    #[must_use]
+    pub fn add(mut self, key: K, dispatcher: Dispatcher<R>) -> Self {
+        self.dispatchers.insert(key, dispatcher);
+        self
+    }
+
+    /// Returns the dispatcher identified by `key`.
+    #[must_use]
+    pub fn dispatcher(&self, key: &K) -> Option<&Dispatcher<R>> {
+        self.dispatchers.get(key)
+    }
+
+    /// Starts all the dispatchers with the default [polling] listeners.
+    ///
+    /// Returns when all the dispatchers are stopped.
+    ///
+    /// [polling]: crate::dispatching::update_listeners::polling_default
+    pub async fn dispatch(&mut self)
+    where
+        R: Requester + Clone,
+        <R as Requester>::GetUpdatesFaultTolerant: Send,
+    {
+        future::join_all(self.dispatchers.values_mut().map(Dispatcher::dispatch)).await;
+    }
+
+    /// Starts the dispatchers with custom `update_listeners`, keyed the same
+    /// way as the dispatchers.
+    ///
+    /// Dispatchers without a listener aren't started. Returns when all the
+    /// started dispatchers are stopped.
+    pub async fn dispatch_with_listeners<'a, UListener, ListenerE, Eh>(
+        &'a mut self,
+        mut update_listeners: HashMap<K, UListener>,
+        update_listener_error_handler: Arc<Eh>,
+    ) where
+        UListener: UpdateListener<ListenerE> + 'a,
+        Eh: ErrorHandler<ListenerE> + 'a,
+        ListenerE: Debug,
+        R: Requester + Clone,
+    {
+        let dispatching = self.dispatchers.iter_mut().filter_map(|(key, dispatcher)| {
+            let listener = update_listeners.remove(key)?;
+            Some(
+                dispatcher
+                    .dispatch_with_listener(listener, Arc::clone(&update_listener_error_handler)),
+            )
+        });
+        let dispatching: Vec<_> = dispatching.collect();
+
+        if !update_listeners.is_empty() {
+            log::warn!("{} update listener(s) have no matching dispatcher", update_listeners.len());
+        }
+
+        future::join_all(dispatching).await;
+    }
+}
+
+impl<K, R> Default for MultiDispatcher<K, R>
+where
+    K: Eq + Hash,
+    R: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{convert::Infallible, sync::Mutex};
+
+    use futures::StreamExt;
+    use teloxide_core::{
+        types::{Message, Update},
+        Bot,
+    };
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use crate::{
+        dispatching::{update_listeners::StatefulListener, DispatcherHandlerRx},
+        error_handlers::LoggingErrorHandler,
+        mock::message_update,
+    };
+
+    fn dispatcher(received: &Arc<Mutex<Vec<String>>>) -> Dispatcher<Bot> {
+        let received = Arc::clone(received);
+
+        Dispatcher::new(Bot::new("Doesn't matter here")).messages_handler(
+            move |rx: DispatcherHandlerRx<Bot, Message>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    let text = cx.update.text().unwrap();
+                    if text == "panic" {
+                        panic!("Oh no");
+                    }
+
+                    received.lock().unwrap().push(text.to_owned());
+                    async {}
+                })
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn each_dispatcher_receives_its_own_updates() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = MultiDispatcher::new()
+            .add("first", dispatcher(&first))
+            .add("second", dispatcher(&second));
+
+        let listener = |updates: Vec<Update>| {
+            let stream = futures::stream::iter(updates.into_iter().map(Ok::<_, Infallible>));
+            StatefulListener::from_stream_without_graceful_shutdown(stream)
+        };
+        let mut listeners = HashMap::new();
+        listeners.insert("first", listener(vec![message_update(1, "A"), message_update(2, "B")]));
+        listeners
+            .insert("second", listener(vec![message_update(1, "panic"), message_update(2, "C")]));
+
+        dispatcher.dispatch_with_listeners(listeners, LoggingErrorHandler::new()).await;
+
+        assert_eq!(*first.lock().unwrap(), vec!["A".to_owned(), "B".to_owned()]);
+        // The panic stops only the handler of the second bot.
+        assert!(second.lock().unwrap().is_empty());
+    }
+}
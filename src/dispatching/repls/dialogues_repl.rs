@@ -24,7 +24,7 @@ use teloxide_core::{requests::Requester, types::Message};
 /// [`Dispatcher`]: crate::dispatching::Dispatcher
 /// [`InMemStorage`]: crate::dispatching::dialogue::InMemStorage
 #[cfg(feature = "ctrlc_handler")]
-pub async fn dialogues_repl<'a, R, H, D, Fut>(requester: R, handler: H)
+pub async fn dialogues_repl<R, H, D, Fut>(requester: R, handler: H)
 where
     H: Fn(UpdateWithCx<R, Message>, D) -> Fut + Send + Sync + 'static,
     D: Clone + Default + Send + 'static,
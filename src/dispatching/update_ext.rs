@@ -0,0 +1,151 @@
+use teloxide_core::types::{Update, UpdateKind, User};
+
+/// An extension trait to get the chat and the user of an [`Update`] of any
+/// kind.
+///
+/// This is useful for the logic which doesn't depend on the update kind, e.g.
+/// to ignore updates from blocked users in [`Dispatcher::updates_handler`].
+///
+/// [`Dispatcher::updates_handler`]: crate::dispatching::Dispatcher::updates_handler
+pub trait UpdateExt {
+    /// Returns the ID of the chat where the update has happened.
+    ///
+    /// Returns `None` for updates which aren't bound to a chat: inline
+    /// queries, chosen inline results, callback queries from inline messages,
+    /// shipping and pre-checkout queries, polls and poll answers.
+    #[must_use]
+    fn chat_id(&self) -> Option<i64>;
+
+    /// Returns the user who has caused the update.
+    ///
+    /// Returns `None` for updates without a user: channel posts and polls.
+    ///
+    /// Unlike [`Update::user`], this also returns the user who has changed
+    /// the chat member status in `MyChatMember` and `ChatMember` updates.
+    #[must_use]
+    fn sender(&self) -> Option<&User>;
+}
+
+impl UpdateExt for Update {
+    fn chat_id(&self) -> Option<i64> {
+        match &self.kind {
+            UpdateKind::Message(message)
+            | UpdateKind::EditedMessage(message)
+            | UpdateKind::ChannelPost(message)
+            | UpdateKind::EditedChannelPost(message) => Some(message.chat.id),
+            UpdateKind::CallbackQuery(query) => query.message.as_ref().map(|m| m.chat.id),
+            UpdateKind::MyChatMember(updated) | UpdateKind::ChatMember(updated) => {
+                Some(updated.chat.id)
+            }
+            UpdateKind::InlineQuery(_)
+            | UpdateKind::ChosenInlineResult(_)
+            | UpdateKind::ShippingQuery(_)
+            | UpdateKind::PreCheckoutQuery(_)
+            | UpdateKind::Poll(_)
+            | UpdateKind::PollAnswer(_) => None,
+        }
+    }
+
+    fn sender(&self) -> Option<&User> {
+        match &self.kind {
+            UpdateKind::Message(message)
+            | UpdateKind::EditedMessage(message)
+            | UpdateKind::ChannelPost(message)
+            | UpdateKind::EditedChannelPost(message) => message.from(),
+            UpdateKind::InlineQuery(query) => Some(&query.from),
+            UpdateKind::ChosenInlineResult(result) => Some(&result.from),
+            UpdateKind::CallbackQuery(query) => Some(&query.from),
+            UpdateKind::ShippingQuery(query) => Some(&query.from),
+            UpdateKind::PreCheckoutQuery(query) => Some(&query.from),
+            UpdateKind::PollAnswer(answer) => Some(&answer.user),
+            UpdateKind::MyChatMember(updated) | UpdateKind::ChatMember(updated) => {
+                Some(&updated.from)
+            }
+            UpdateKind::Poll(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::user;
+
+    fn update(json: serde_json::Value) -> Update {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn message() {
+        let update = update(serde_json::json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 1,
+                "date": 1_600_000_000,
+                "chat": { "id": -100, "type": "supergroup", "title": "Chat" },
+                "from": user(42),
+                "text": "Hello"
+            }
+        }));
+
+        assert_eq!(update.chat_id(), Some(-100));
+        assert_eq!(update.sender().map(|u| u.id), Some(42));
+    }
+
+    #[test]
+    fn callback_query() {
+        let update = update(serde_json::json!({
+            "update_id": 1,
+            "callback_query": {
+                "id": "1",
+                "from": user(42),
+                "chat_instance": "1",
+                "message": {
+                    "message_id": 1,
+                    "date": 1_600_000_000,
+                    "chat": { "id": 17, "type": "private", "first_name": "Hirrolot" },
+                    "from": user(1),
+                    "text": "Hello"
+                },
+                "data": "data"
+            }
+        }));
+
+        assert_eq!(update.chat_id(), Some(17));
+        assert_eq!(update.sender().map(|u| u.id), Some(42));
+    }
+
+    #[test]
+    fn inline_query_has_no_chat() {
+        let update = update(serde_json::json!({
+            "update_id": 1,
+            "inline_query": {
+                "id": "1",
+                "from": user(42),
+                "query": "query",
+                "offset": ""
+            }
+        }));
+
+        assert_eq!(update.chat_id(), None);
+        assert_eq!(update.sender().map(|u| u.id), Some(42));
+    }
+
+    #[test]
+    fn chat_member() {
+        let update = update(serde_json::json!({
+            "update_id": 1,
+            "chat_member": {
+                "chat": { "id": -100, "type": "supergroup", "title": "Chat" },
+                "from": user(42),
+                "date": 1_600_000_000,
+                "old_chat_member": { "user": user(1), "status": "left" },
+                "new_chat_member": { "user": user(1), "status": "member" }
+            }
+        }));
+
+        assert_eq!(update.chat_id(), Some(-100));
+        assert_eq!(update.sender().map(|u| u.id), Some(42));
+    }
+}
@@ -5,6 +5,11 @@
 //!  - [`polling_default`], which returns a default long polling listener.
 //!  - [`polling`], which returns a long/short polling listener with your
 //!    configuration.
+//!  - [`from_stream`] and [`channel`], which return listeners of updates from
+//!    a custom source (e.g. from a message queue or constructed in tests).
+//!  - [`from_file`], which replays updates recorded by
+//!    [`Dispatcher::record_updates`].
+//!  - [`merge`], which combines two listeners into one.
 //!
 //! And then you can extract updates from it and pass them directly to a
 //! dispatcher.
@@ -95,6 +100,7 @@
 //! See the [README FAQ about webhooks](https://github.com/teloxide/teloxide/blob/master/README.md#faq).
 //!
 //! [`UpdateListener`]: UpdateListener
+//! [`Dispatcher::record_updates`]: crate::dispatching::Dispatcher::record_updates
 //! [`polling_default`]: polling_default
 //! [`polling`]: polling()
 //! [`Box::get_updates`]: crate::requests::Requester::get_updates
@@ -112,14 +118,20 @@ use crate::{
     types::{AllowedUpdate, Update},
 };
 
+mod from_stream;
+mod merge;
 mod polling;
 mod stateful_listener;
 
 pub use self::{
-    polling::{polling, polling_default},
+    from_stream::{channel, from_file, from_stream},
+    merge::merge,
+    polling::{polling, polling_default, polling_stream, polling_with_heartbeat},
     stateful_listener::StatefulListener,
 };
 
+pub(crate) use self::polling::drop_pending_updates;
+
 /// An update listener.
 ///
 /// Implementors of this trait allow getting updates from Telegram.
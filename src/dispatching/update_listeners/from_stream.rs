@@ -0,0 +1,142 @@
+use std::{io, path::Path};
+
+use futures::{stream, Stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::{
+    dispatching::{
+        stop_token::{AsyncStopFlag, AsyncStopToken},
+        update_listeners::{stateful_listener::StatefulListener, UpdateListener},
+    },
+    types::Update,
+};
+
+/// Returns an update listener which yields updates from `stream`.
+///
+/// This allows to feed a dispatcher with updates from a custom source (e.g. a
+/// message queue) or with updates constructed manually, e.g. in tests.
+///
+/// The listener stops when `stream` ends or when its stop token is used; in
+/// the latter case the rest of `stream` is not consumed.
+///
+/// The listener never fails, so its error type `E` can be anything, e.g. the
+/// error type of [`polling`] to [`merge`] them. If it's not constrained
+/// otherwise, use [`Infallible`].
+///
+/// See also: [`channel`].
+///
+/// [`polling`]: crate::dispatching::update_listeners::polling()
+/// [`merge`]: crate::dispatching::update_listeners::merge
+/// [`Infallible`]: std::convert::Infallible
+pub fn from_stream<S, E>(stream: S) -> impl UpdateListener<E>
+where
+    S: Stream<Item = Update> + Send + Unpin + 'static,
+    E: 'static,
+{
+    from_fallible_stream(stream.map(Ok))
+}
+
+/// Returns an update listener which replays updates from the file at `path`.
+///
+/// The file must contain one JSON-serialized update per line, e.g. as written
+/// by [`Dispatcher::record_updates`]. Lines which cannot be parsed are yielded
+/// as errors, so they are passed into the error handler of the dispatcher.
+///
+/// [`Dispatcher::record_updates`]: crate::dispatching::Dispatcher::record_updates
+pub async fn from_file<P>(path: P) -> io::Result<impl UpdateListener<serde_json::Error>>
+where
+    P: AsRef<Path>,
+{
+    let updates: Vec<_> = tokio::fs::read_to_string(path)
+        .await?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect();
+
+    Ok(from_fallible_stream(stream::iter(updates)))
+}
+
+fn from_fallible_stream<S, E>(stream: S) -> impl UpdateListener<E>
+where
+    S: Stream<Item = Result<Update, E>> + Send + Unpin + 'static,
+    E: 'static,
+{
+    struct State<S> {
+        stream: S,
+        flag: AsyncStopFlag,
+        token: AsyncStopToken,
+    }
+
+    fn yield_updates<S, E>(st: &mut State<S>) -> impl Stream<Item = Result<Update, E>> + Send + '_
+    where
+        S: Stream<Item = Result<Update, E>> + Send + Unpin,
+    {
+        let State { stream, flag, .. } = st;
+        stream.take_until(flag)
+    }
+
+    let (token, flag) = AsyncStopToken::new_pair();
+    let state = State { stream, flag, token };
+
+    let stop_token = |st: &mut State<_>| st.token.clone();
+
+    StatefulListener::new(state, yield_updates, stop_token)
+}
+
+/// Returns a sender of updates and an update listener which yields the sent
+/// updates.
+///
+/// The listener stops when all the senders are dropped or when its stop token
+/// is used. Like with [`from_stream`], its error type `E` can be anything.
+///
+/// See also: [`from_stream`].
+pub fn channel<E>() -> (mpsc::UnboundedSender<Update>, impl UpdateListener<E>)
+where
+    E: 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    (tx, from_stream(UnboundedReceiverStream::new(rx)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{
+        dispatching::{Dispatcher, DispatcherHandlerRx},
+        error_handlers::LoggingErrorHandler,
+        mock::message_update,
+        Bot,
+    };
+
+    #[tokio::test]
+    async fn dispatcher_processes_sent_updates() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here")).updates_handler({
+            let received = Arc::clone(&received);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    received.lock().unwrap().push(cx.update.id);
+                    async {}
+                })
+            }
+        });
+
+        let (tx, listener) = channel::<Infallible>();
+        tx.send(message_update(1, "Hello")).unwrap();
+        tx.send(message_update(2, "Hello")).unwrap();
+        drop(tx);
+
+        dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::new()).await;
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use futures::stream::{self, Select};
+
+use crate::{
+    dispatching::{
+        stop_token::StopToken,
+        update_listeners::{AsUpdateStream, UpdateListener},
+    },
+    types::AllowedUpdate,
+};
+
+/// Returns an update listener which yields updates from both `a` and `b`, as
+/// they arrive.
+///
+/// This allows, for example, to inject updates from a custom source (see
+/// [`channel`]) into the updates received by [`polling`].
+///
+/// Errors of both listeners are propagated. The stop token of the returned
+/// listener stops both of them, and the returned listener stops when both of
+/// them are stopped. Hints given to the returned listener are passed to both
+/// of them.
+///
+/// [`channel`]: crate::dispatching::update_listeners::channel
+/// [`polling`]: crate::dispatching::update_listeners::polling()
+pub fn merge<A, B, E>(a: A, b: B) -> impl UpdateListener<E>
+where
+    A: UpdateListener<E>,
+    B: UpdateListener<E>,
+{
+    Merge { a, b }
+}
+
+struct Merge<A, B> {
+    a: A,
+    b: B,
+}
+
+struct MergeStopToken<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> StopToken for MergeStopToken<A, B>
+where
+    A: StopToken,
+    B: StopToken,
+{
+    fn stop(self) {
+        self.a.stop();
+        self.b.stop();
+    }
+}
+
+impl<'a, A, B, E> AsUpdateStream<'a, E> for Merge<A, B>
+where
+    A: AsUpdateStream<'a, E>,
+    B: AsUpdateStream<'a, E>,
+{
+    type Stream = Select<A::Stream, B::Stream>;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        stream::select(self.a.as_stream(), self.b.as_stream())
+    }
+}
+
+impl<A, B, E> UpdateListener<E> for Merge<A, B>
+where
+    A: UpdateListener<E>,
+    B: UpdateListener<E>,
+{
+    type StopToken = MergeStopToken<A::StopToken, B::StopToken>;
+
+    fn stop_token(&mut self) -> Self::StopToken {
+        MergeStopToken { a: self.a.stop_token(), b: self.b.stop_token() }
+    }
+
+    fn hint_allowed_updates(&mut self, hint: &mut dyn Iterator<Item = AllowedUpdate>) {
+        let hint: Vec<_> = hint.collect();
+
+        self.a.hint_allowed_updates(&mut hint.iter().cloned());
+        self.b.hint_allowed_updates(&mut hint.into_iter());
+    }
+
+    fn timeout_hint(&self) -> Option<Duration> {
+        // The longest timeout, so that the dispatcher doesn't check for a
+        // shutdown while one of the listeners is still waiting for updates.
+        self.a.timeout_hint().max(self.b.timeout_hint())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use futures::StreamExt;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use crate::{
+        dispatching::{
+            update_listeners::{channel, polling},
+            Dispatcher, DispatcherHandlerRx,
+        },
+        mock::{message_update, updates_json, MockApi},
+        types::Update,
+        Bot, RequestError,
+    };
+
+    #[tokio::test]
+    async fn polling_and_channel_are_merged() {
+        // `getUpdates` fails once, then returns #1 and #2 until they're confirmed.
+        let failed = AtomicUsize::new(0);
+        let api = MockApi::start(move |_, params| {
+            if failed.fetch_add(1, Ordering::SeqCst) == 0 {
+                return serde_json::json!("not updates");
+            }
+
+            let offset = params["offset"].as_i64().unwrap_or(0).max(1);
+            let updates: Vec<_> =
+                (offset..=2).map(|id| message_update(id as i32, "Hello")).collect();
+            updates_json(&updates)
+        })
+        .await;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let errors = Arc::new(AtomicUsize::new(0));
+
+        let mut dispatcher = Dispatcher::new(Bot::new("Doesn't matter here"));
+        let shutdown_token = dispatcher.shutdown_token();
+        dispatcher = dispatcher.updates_handler({
+            let received = Arc::clone(&received);
+            move |rx: DispatcherHandlerRx<Bot, Update>| {
+                UnboundedReceiverStream::new(rx).for_each(move |cx| {
+                    let mut received = received.lock().unwrap();
+                    received.push(cx.update.id);
+                    if received.len() == 3 {
+                        let _ = shutdown_token.shutdown();
+                    }
+                    async {}
+                })
+            }
+        });
+
+        let main = polling(api.bot(), None, None, None);
+        let (admin_tx, admin) = channel();
+        admin_tx.send(message_update(100, "Hello")).unwrap();
+
+        let error_handler = {
+            let errors = Arc::clone(&errors);
+            move |_: RequestError| {
+                errors.fetch_add(1, Ordering::SeqCst);
+                async {}
+            }
+        };
+        dispatcher.dispatch_with_listener(merge(main, admin), Arc::new(error_handler)).await;
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort_unstable();
+        assert_eq!(received, vec![1, 2, 100]);
+        assert_eq!(errors.load(Ordering::SeqCst), 1);
+
+        // The shutdown has stopped polling, which has confirmed the updates.
+        let (method, params) = api.requests().pop().unwrap();
+        assert_eq!(method, "GetUpdates");
+        assert_eq!(params["offset"], 3);
+    }
+}
@@ -1,7 +1,7 @@
-use std::{convert::TryInto, time::Duration};
+use std::{convert::TryInto, future::Future, time::Duration};
 
 use futures::{
-    future::{ready, Either},
+    future::{self, Either},
     stream::{self, Stream, StreamExt},
 };
 
@@ -38,7 +38,7 @@ where
 /// - `limit`: Limits the number of updates to be retrieved at once. Values
 ///   between 1—100 are accepted.
 /// - `allowed_updates`: A list the types of updates you want to receive.
-/// See [`GetUpdates`] for defaults.
+///   See [`GetUpdates`] for defaults.
 ///
 /// See also: [`polling_default`](polling_default).
 ///
@@ -53,97 +53,54 @@ where
     R: Requester + Send + 'static,
     <R as Requester>::GetUpdatesFaultTolerant: Send,
 {
-    struct State<B: Requester> {
-        bot: B,
-        timeout: Option<u32>,
-        limit: Option<u8>,
-        allowed_updates: Option<Vec<AllowedUpdate>>,
-        offset: i32,
-        flag: AsyncStopFlag,
-        token: AsyncStopToken,
-    }
+    polling_inner(State::new(requester, timeout, limit, allowed_updates))
+}
+
+/// Returns a long/short polling update listener which calls `heartbeat` after
+/// every successful `getUpdates` request.
+///
+/// `heartbeat` is called even if no updates were received, so it can be used
+/// for liveness probes to detect a stalled polling. It is called inline in
+/// the polling loop, so it should be cheap (e.g. store the current time into
+/// an atomic).
+///
+/// The other parameters are the same as of [`polling`](polling()).
+pub fn polling_with_heartbeat<R, H>(
+    requester: R,
+    timeout: Option<Duration>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    heartbeat: H,
+) -> impl UpdateListener<R::Err>
+where
+    R: Requester + Send + 'static,
+    <R as Requester>::GetUpdatesFaultTolerant: Send,
+    H: Fn() + Send + Sync + 'static,
+{
+    let mut state = State::new(requester, timeout, limit, allowed_updates);
+    state.heartbeat = Some(Box::new(heartbeat));
 
+    polling_inner(state)
+}
+
+fn polling_inner<R>(state: State<R>) -> impl UpdateListener<R::Err>
+where
+    R: Requester + Send + 'static,
+    <R as Requester>::GetUpdatesFaultTolerant: Send,
+{
     fn stream<B>(st: &mut State<B>) -> impl Stream<Item = Result<Update, B::Err>> + Send + '_
     where
         B: Requester + Send,
         <B as Requester>::GetUpdatesFaultTolerant: Send,
     {
-        stream::unfold(st, move |state| async move {
-            let State { timeout, limit, allowed_updates, bot, offset, flag, .. } = &mut *state;
-
-            if flag.is_stopped() {
-                let mut req = bot.get_updates_fault_tolerant();
-
-                req.payload_mut().0 = GetUpdates {
-                    offset: Some(*offset),
-                    timeout: Some(0),
-                    limit: Some(1),
-                    allowed_updates: allowed_updates.take(),
-                };
-
-                return match req.send().await {
-                    Ok(_) => None,
-                    Err(err) => Some((Either::Left(stream::once(ready(Err(err)))), state)),
-                };
-            }
-
-            let mut req = bot.get_updates_fault_tolerant();
-            req.payload_mut().0 = GetUpdates {
-                offset: Some(*offset),
-                timeout: *timeout,
-                limit: *limit,
-                allowed_updates: allowed_updates.take(),
-            };
-
-            let updates = match req.send().await {
-                Err(err) => return Some((Either::Left(stream::once(ready(Err(err)))), state)),
-                Ok(SemiparsedVec(updates)) => {
-                    // Set offset to the last update's id + 1
-                    if let Some(upd) = updates.last() {
-                        let id: i32 = match upd {
-                            Ok(ok) => ok.id,
-                            Err((value, _)) => value["update_id"]
-                                .as_i64()
-                                .expect("The 'update_id' field must always exist in Update")
-                                .try_into()
-                                .expect("update_id must be i32"),
-                        };
-
-                        *offset = id + 1;
-                    }
-
-                    for update in &updates {
-                        if let Err((value, e)) = update {
-                            log::error!(
-                                "Cannot parse an update.\nError: {:?}\nValue: {}\n\
-                            This is a bug in teloxide-core, please open an issue here: \
-                            https://github.com/teloxide/teloxide-core/issues.",
-                                e,
-                                value
-                            );
-                        }
-                    }
-
-                    updates.into_iter().filter_map(Result::ok).map(Ok)
-                }
-            };
-
-            Some((Either::Right(stream::iter(updates)), state))
+        stream::unfold(st, |state| async move {
+            let updates = next_updates(&mut *state).await?;
+            Some((stream::iter(updates), state))
         })
         .flatten()
     }
 
-    let (token, flag) = AsyncStopToken::new_pair();
-
-    let state = State {
-        bot: requester,
-        timeout: timeout.map(|t| t.as_secs().try_into().expect("timeout is too big")),
-        limit,
-        allowed_updates,
-        offset: 0,
-        flag,
-        token,
-    };
+    let timeout = state.timeout.map(|t| Duration::from_secs(t.into()));
 
     let stop_token = |st: &mut State<_>| st.token.clone();
 
@@ -158,6 +115,233 @@ where
     StatefulListener::new_with_hints(state, stream, stop_token, hint_allowed_updates, timeout_hint)
 }
 
+/// Returns a stream of updates received by long/short polling.
+///
+/// Unlike [`polling`](polling()), this function returns a plain [`Stream`]
+/// which owns the requester, so it can be freely composed with [`StreamExt`]
+/// combinators. Updates are yielded one by one; a batch is requested only
+/// when all the updates of the previous batch are consumed, which also
+/// confirms them to Telegram. Errors of `getUpdates` are yielded as stream
+/// items, and polling continues after them.
+///
+/// The parameters are the same as of [`polling`](polling()).
+///
+/// [`StreamExt`]: futures::StreamExt
+pub fn polling_stream<R>(
+    requester: R,
+    timeout: Option<Duration>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+) -> impl Stream<Item = Result<Update, R::Err>> + Send
+where
+    R: Requester + Send + 'static,
+    <R as Requester>::GetUpdatesFaultTolerant: Send,
+{
+    let state = State::new(requester, timeout, limit, allowed_updates);
+
+    stream::unfold(state, |mut state| async move {
+        let updates = next_updates(&mut state).await?;
+        Some((stream::iter(updates), state))
+    })
+    .flatten()
+}
+
+struct State<B: Requester> {
+    bot: B,
+    timeout: Option<u32>,
+    limit: Option<u8>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    offset: i32,
+    flag: AsyncStopFlag,
+    token: AsyncStopToken,
+    heartbeat: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl<B> State<B>
+where
+    B: Requester,
+{
+    fn new(
+        bot: B,
+        timeout: Option<Duration>,
+        limit: Option<u8>,
+        allowed_updates: Option<Vec<AllowedUpdate>>,
+    ) -> Self {
+        let (token, flag) = AsyncStopToken::new_pair();
+
+        Self {
+            bot,
+            timeout: timeout.map(|t| t.as_secs().try_into().expect("timeout is too big")),
+            limit,
+            allowed_updates,
+            offset: 0,
+            flag,
+            token,
+            heartbeat: None,
+        }
+    }
+}
+
+/// Requests the next batch of updates.
+///
+/// Returns `None` if the listener is stopped and the already received updates
+/// are confirmed.
+async fn next_updates<B>(state: &mut State<B>) -> Option<Vec<Result<Update, B::Err>>>
+where
+    B: Requester,
+{
+    let State { timeout, limit, allowed_updates, bot, offset, flag, heartbeat, .. } = state;
+
+    if flag.is_stopped() {
+        return confirm_updates(bot, *offset, allowed_updates.take()).await;
+    }
+
+    let mut req = bot.get_updates_fault_tolerant();
+    req.payload_mut().0 = GetUpdates {
+        offset: Some(*offset),
+        timeout: *timeout,
+        limit: *limit,
+        allowed_updates: allowed_updates.take(),
+    };
+
+    // A long poll can take up to `timeout`, so it's aborted as soon as the
+    // listener is stopped. Nothing is received by an aborted request, so only
+    // the previous updates have to be confirmed.
+    let res = match unless_stopped(req.send(), flag).await {
+        Some(res) => res,
+        None => return confirm_updates(bot, *offset, None).await,
+    };
+
+    let updates = match res {
+        Err(err) => return Some(vec![Err(err)]),
+        Ok(SemiparsedVec(updates)) => {
+            if let Some(heartbeat) = heartbeat {
+                heartbeat();
+            }
+
+            accept_batch(updates, offset)
+        }
+    };
+
+    Some(updates)
+}
+
+/// Confirms the updates before `offset`, finishing the polling.
+///
+/// The request is built before the returned future is awaited, so the future
+/// doesn't borrow `bot`, which would make it `!Send` for `B: !Sync`.
+fn confirm_updates<B>(
+    bot: &B,
+    offset: i32,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+) -> impl Future<Output = Option<Vec<Result<Update, B::Err>>>>
+where
+    B: Requester,
+{
+    let mut req = bot.get_updates_fault_tolerant();
+    req.payload_mut().0 =
+        GetUpdates { offset: Some(offset), timeout: Some(0), limit: Some(1), allowed_updates };
+
+    async move {
+        match req.send().await {
+            Ok(_) => None,
+            Err(err) => Some(vec![Err(err)]),
+        }
+    }
+}
+
+/// Awaits `fut`, unless `flag` is stopped first.
+///
+/// Returns `None` if `flag` is stopped, dropping `fut`.
+async fn unless_stopped<F>(fut: F, flag: &mut AsyncStopFlag) -> Option<F::Output>
+where
+    F: Future,
+{
+    futures::pin_mut!(fut);
+
+    match future::select(fut, flag).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
+
+/// Confirms all the updates which are pending on the Telegram side, so that
+/// they are never received.
+pub(crate) async fn drop_pending_updates<R>(requester: &R)
+where
+    R: Requester,
+{
+    // A negative offset returns the last updates, so this receives the last
+    // pending update, if any.
+    let mut req = requester.get_updates_fault_tolerant();
+    req.payload_mut().0 =
+        GetUpdates { offset: Some(-1), timeout: Some(0), limit: Some(1), allowed_updates: None };
+
+    let last = match req.send().await {
+        Ok(SemiparsedVec(updates)) => match updates.last() {
+            Some(upd) => update_id(upd),
+            None => return,
+        },
+        Err(e) => {
+            log::error!("Failed to get pending updates: {:?}", e);
+            return;
+        }
+    };
+
+    let mut req = requester.get_updates_fault_tolerant();
+    req.payload_mut().0 = GetUpdates {
+        offset: Some(last + 1),
+        timeout: Some(0),
+        limit: Some(1),
+        allowed_updates: None,
+    };
+
+    match req.send().await {
+        Ok(_) => log::info!("Pending updates up to #{} have been dropped", last),
+        Err(e) => log::error!("Failed to drop pending updates: {:?}", e),
+    }
+}
+
+/// Sets `offset` past the received updates and returns the parsed ones.
+///
+/// Updates which cannot be parsed (e.g. the ones of kinds unknown to
+/// teloxide-core) are logged and skipped, but still confirmed, so that they
+/// don't stall polling.
+fn accept_batch<E>(
+    updates: Vec<Result<Update, (serde_json::Value, serde_json::Error)>>,
+    offset: &mut i32,
+) -> Vec<Result<Update, E>> {
+    // Set offset to the last update's id + 1
+    if let Some(upd) = updates.last() {
+        *offset = update_id(upd) + 1;
+    }
+
+    for update in &updates {
+        if let Err((value, e)) = update {
+            log::error!(
+                "Cannot parse an update.\nError: {:?}\nValue: {}\n\
+                 This is a bug in teloxide-core, please open an issue here: \
+                 https://github.com/teloxide/teloxide-core/issues.",
+                e,
+                value
+            );
+        }
+    }
+
+    updates.into_iter().filter_map(Result::ok).map(Ok).collect()
+}
+
+fn update_id(update: &Result<Update, (serde_json::Value, serde_json::Error)>) -> i32 {
+    match update {
+        Ok(ok) => ok.id,
+        Err((value, _)) => value["update_id"]
+            .as_i64()
+            .expect("The 'update_id' field must always exist in Update")
+            .try_into()
+            .expect("update_id must be i32"),
+    }
+}
+
 async fn delete_webhook_if_setup<R>(requester: &R)
 where
     R: Requester,
@@ -170,7 +354,7 @@ where
         }
     };
 
-    let is_webhook_setup = !webhook_info.url.is_none();
+    let is_webhook_setup = !webhook_info.url.is_empty();
 
     if is_webhook_setup {
         if let Err(e) = requester.delete_webhook().send().await {
@@ -191,3 +375,137 @@ fn polling_is_send() {
 
     fn assert_send(_: &impl Send) {}
 }
+
+#[test]
+fn polling_stream_is_send() {
+    let bot = crate::Bot::new("TOKEN");
+    let stream = polling_stream(bot, None, None, None);
+
+    assert_send(&stream);
+
+    fn assert_send(_: &impl Send) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    use crate::{
+        dispatching::{update_listeners::AsUpdateStream, Dispatcher, DispatcherHandlerRx},
+        error_handlers::LoggingErrorHandler,
+        mock::{callback_query_update, message_update, updates_json, MockApi},
+        types::Message,
+        Bot,
+    };
+
+    #[test]
+    fn unparsable_updates_are_skipped_and_confirmed() {
+        let mut updates = updates_json(&[message_update(1, "Hello"), callback_query_update(3)]);
+        // An update which doesn't match the `Update` type.
+        let unparsable =
+            serde_json::json!({ "update_id": 2, "message": { "message_id": "not a number" } });
+        updates.as_array_mut().unwrap().insert(1, unparsable);
+
+        let SemiparsedVec(updates) = serde_json::from_value(updates).unwrap();
+        assert_eq!(updates.len(), 3);
+
+        let mut offset = 0;
+        let updates = accept_batch::<Infallible>(updates, &mut offset);
+
+        let ids: Vec<_> = updates.into_iter().map(|upd| upd.unwrap().id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(offset, 4);
+    }
+
+    #[tokio::test]
+    async fn polling_stream_yields_updates_one_by_one() {
+        // A batch of 3 updates, then no updates.
+        let api = MockApi::start(|_, params| match params["offset"].as_i64() {
+            Some(0) => {
+                updates_json(&(1..=3).map(|id| message_update(id, "Hello")).collect::<Vec<_>>())
+            }
+            _ => serde_json::json!([]),
+        })
+        .await;
+
+        let stream = polling_stream(api.bot(), None, None, None);
+        tokio::pin!(stream);
+
+        for id in 1..=3 {
+            assert_eq!(stream.next().await.unwrap().unwrap().id, id);
+        }
+        assert_eq!(api.requests().len(), 1);
+
+        // The next batch is requested past the consumed updates.
+        let next = tokio::time::timeout(Duration::from_millis(100), stream.next()).await;
+        assert!(next.is_err());
+
+        let requests = api.requests();
+        assert!(requests.iter().all(|(method, _)| method == "GetUpdates"));
+        assert_eq!(requests[1].1["offset"], 4);
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_long_poll() {
+        // There are no updates, so the long poll lasts for 50 seconds.
+        let api = MockApi::start(|_, _| serde_json::json!([])).await;
+
+        let mut dispatcher =
+            Dispatcher::new(api.bot()).messages_handler(|rx: DispatcherHandlerRx<Bot, Message>| {
+                UnboundedReceiverStream::new(rx).for_each(|_| async {})
+            });
+        let shutdown_token = dispatcher.shutdown_token();
+
+        let listener = polling(api.bot(), Some(Duration::from_secs(50)), None, None);
+        let dispatch = dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::new());
+        let shutdown = async {
+            // Let the long poll start.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            shutdown_token.shutdown().unwrap().await;
+        };
+
+        tokio::time::timeout(Duration::from_secs(5), future::join(dispatch, shutdown))
+            .await
+            .expect("dispatching isn't shut down during the long poll");
+
+        // The aborted long poll and the confirmation of the (absent) updates.
+        let timeouts: Vec<_> =
+            api.requests().into_iter().map(|(_, params)| params["timeout"].clone()).collect();
+        assert_eq!(timeouts, vec![serde_json::json!(50), serde_json::json!(0)]);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_is_called_on_empty_batches() {
+        let api = MockApi::start(|_, _| serde_json::json!([])).await;
+        let heartbeats = Arc::new(AtomicUsize::new(0));
+
+        let mut listener = polling_with_heartbeat(api.bot(), None, None, None, {
+            let heartbeats = Arc::clone(&heartbeats);
+            move || {
+                heartbeats.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let stream = listener.as_stream();
+        tokio::pin!(stream);
+
+        // No updates are received, so the stream never yields.
+        let next = tokio::time::timeout(Duration::from_millis(500), stream.next()).await;
+        assert!(next.is_err());
+
+        let requests = api.requests();
+        assert!(requests.len() > 1);
+        assert!(requests.iter().all(|(method, _)| method == "GetUpdates"));
+        assert!(heartbeats.load(Ordering::SeqCst) >= requests.len() - 1);
+    }
+}
@@ -1,14 +1,26 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{self, Poll},
+};
+
 use crate::dispatching::dialogue::GetChatId;
 use teloxide_core::{
     payloads::{
-        SendAnimationSetters, SendAudioSetters, SendContactSetters, SendDocumentSetters,
-        SendLocationSetters, SendMediaGroupSetters, SendMessageSetters, SendPhotoSetters,
-        SendStickerSetters, SendVenueSetters, SendVideoNoteSetters, SendVideoSetters,
-        SendVoiceSetters,
+        AnswerCallbackQuerySetters, EditMessageTextSetters, SendAnimationSetters, SendAudioSetters,
+        SendContactSetters, SendDocumentSetters, SendLocationSetters, SendMediaGroupSetters,
+        SendMessageSetters, SendPhotoSetters, SendStickerSetters, SendVenueSetters,
+        SendVideoNoteSetters, SendVideoSetters, SendVoiceSetters,
     },
-    requests::{Request, Requester},
-    types::{ChatId, InputFile, InputMedia, Message},
+    requests::{HasPayload, Output, Request, Requester},
+    types::{CallbackQuery, ChatId, InlineKeyboardMarkup, InputFile, InputMedia, Message},
 };
+use tokio::sync::OwnedSemaphorePermit;
+use url::Url;
 
 /// A [`Dispatcher`]'s handler's context of a bot and an update.
 ///
@@ -16,10 +28,34 @@ use teloxide_core::{
 /// overview.
 ///
 /// [`Dispatcher`]: crate::dispatching::Dispatcher
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UpdateWithCx<R, Upd> {
     pub requester: R,
     pub update: Upd,
+
+    /// Occupies a slot of [`Dispatcher::max_concurrent_updates`] until the
+    /// update (with all its clones) is dropped.
+    ///
+    /// [`Dispatcher::max_concurrent_updates`]: crate::dispatching::Dispatcher::max_concurrent_updates
+    pub(crate) permit: Option<Arc<OwnedSemaphorePermit>>,
+
+    /// Is set once a callback query is answered via [`UpdateWithCx::answer`],
+    /// so that [`FnHandler::auto_answer_callback_queries`] doesn't answer it
+    /// once again.
+    ///
+    /// [`FnHandler::auto_answer_callback_queries`]: crate::dispatching::FnHandler::auto_answer_callback_queries
+    pub(crate) answered: Option<Arc<AtomicBool>>,
+}
+
+impl<R, Upd> UpdateWithCx<R, Upd> {
+    /// Creates a context of `update` received by `requester`.
+    ///
+    /// It's the only way to construct [`UpdateWithCx`] outside teloxide, e.g.
+    /// to test handlers.
+    #[must_use]
+    pub fn new(requester: R, update: Upd) -> Self {
+        Self { requester, update, permit: None, answered: None }
+    }
 }
 
 impl<Upd, R> GetChatId for UpdateWithCx<R, Upd>
@@ -245,3 +281,273 @@ where
         self.requester.send_dice(self.update.chat.id)
     }
 }
+
+impl<R> UpdateWithCx<R, CallbackQuery>
+where
+    R: Requester,
+{
+    /// Answers the callback query without a notification, just to stop the
+    /// progress bar on the button.
+    pub fn answer(&self) -> CallbackQueryAnswer<R::AnswerCallbackQuery> {
+        CallbackQueryAnswer {
+            inner: self.requester.answer_callback_query(self.update.id.clone()),
+            answered: self.answered.clone(),
+        }
+    }
+
+    /// Answers the callback query with a notification of `text`.
+    ///
+    /// If `show_alert` is `true`, the notification is shown as an alert
+    /// instead of a toast at the top of the chat screen.
+    pub fn answer_with_text<T>(
+        &self,
+        text: T,
+        show_alert: bool,
+    ) -> CallbackQueryAnswer<R::AnswerCallbackQuery>
+    where
+        T: Into<String>,
+    {
+        self.answer().text(text).show_alert(show_alert)
+    }
+
+    /// Edits the text and the inline keyboard of the message with the callback
+    /// button in one request, e.g. to switch a page of an inline menu.
+    ///
+    /// Returns `None` if the message is not available, i.e. the button is
+    /// attached to an inline message (use [`Requester::edit_message_text_inline`]
+    /// with [`CallbackQuery::inline_message_id`] instead) or the message is
+    /// too old.
+    ///
+    /// [`Requester::edit_message_text_inline`]: crate::requests::Requester::edit_message_text_inline
+    /// [`CallbackQuery::inline_message_id`]: crate::types::CallbackQuery::inline_message_id
+    pub fn edit_message<T>(
+        &self,
+        text: T,
+        markup: InlineKeyboardMarkup,
+    ) -> Option<R::EditMessageText>
+    where
+        T: Into<String>,
+    {
+        let message = self.update.message.as_ref()?;
+
+        Some(
+            self.requester
+                .edit_message_text(message.chat.id, message.id, text)
+                .reply_markup(markup),
+        )
+    }
+
+    /// Returns the data associated with the callback button, if any.
+    pub fn data(&self) -> Option<&str> {
+        self.update.data.as_deref()
+    }
+
+    /// Answers the callback query of a [game] button by opening `url`.
+    ///
+    /// `url` must point to your game (as set up via [@BotFather]) or be a
+    /// `t.me/your_bot?start=XXXX` link; other URLs are rejected by Telegram.
+    /// Answers to callback queries of non-game buttons should rather show a
+    /// notification via [`AnswerCallbackQuerySetters::text`], since a URL and
+    /// a notification are not meant to be used together.
+    ///
+    /// [game]: https://core.telegram.org/bots/api#games
+    /// [@BotFather]: https://t.me/botfather
+    /// [`AnswerCallbackQuerySetters::text`]: crate::payloads::AnswerCallbackQuerySetters::text
+    pub fn answer_with_url(&self, url: Url) -> CallbackQueryAnswer<R::AnswerCallbackQuery> {
+        self.answer().url(url)
+    }
+}
+
+/// An answer to a callback query, returned by [`UpdateWithCx::answer`] and
+/// the other `answer_*` methods of callback queries.
+///
+/// It's sent like the wrapped request, but once it has been sent
+/// successfully, [`FnHandler::auto_answer_callback_queries`] doesn't answer
+/// the query once again.
+///
+/// [`FnHandler::auto_answer_callback_queries`]: crate::dispatching::FnHandler::auto_answer_callback_queries
+#[pin_project::pin_project]
+#[must_use = "Requests are lazy and do nothing unless sent"]
+pub struct CallbackQueryAnswer<Req> {
+    #[pin]
+    inner: Req,
+    answered: Option<Arc<AtomicBool>>,
+}
+
+impl<Req> HasPayload for CallbackQueryAnswer<Req>
+where
+    Req: HasPayload,
+{
+    type Payload = Req::Payload;
+
+    fn payload_mut(&mut self) -> &mut Self::Payload {
+        self.inner.payload_mut()
+    }
+
+    fn payload_ref(&self) -> &Self::Payload {
+        self.inner.payload_ref()
+    }
+}
+
+impl<Req> Request for CallbackQueryAnswer<Req>
+where
+    Req: Request,
+{
+    type Err = Req::Err;
+
+    type Send = MarkAnswered<Req::Send>;
+
+    type SendRef = MarkAnswered<Req::SendRef>;
+
+    fn send(self) -> Self::Send {
+        MarkAnswered { inner: self.inner.send(), answered: self.answered }
+    }
+
+    fn send_ref(&self) -> Self::SendRef {
+        MarkAnswered { inner: self.inner.send_ref(), answered: self.answered.clone() }
+    }
+}
+
+/// Allows to `.await` the answer if the wrapped request can be awaited (e.g.
+/// with [`AutoSend`]).
+///
+/// [`AutoSend`]: crate::adaptors::AutoSend
+impl<Req> Future for CallbackQueryAnswer<Req>
+where
+    Req: Request + Future<Output = Result<Output<Req>, Req::Err>>,
+{
+    type Output = <Req as Future>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        mark_answered(this.inner.poll(cx), this.answered)
+    }
+}
+
+/// A future of a sent [`CallbackQueryAnswer`].
+#[pin_project::pin_project]
+pub struct MarkAnswered<F> {
+    #[pin]
+    inner: F,
+    answered: Option<Arc<AtomicBool>>,
+}
+
+impl<F, T, E> Future for MarkAnswered<F>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        mark_answered(this.inner.poll(cx), this.answered)
+    }
+}
+
+/// Marks the query as answered only if the answer has succeeded, so that a
+/// failed answer is retried by [`FnHandler::auto_answer_callback_queries`].
+///
+/// [`FnHandler::auto_answer_callback_queries`]: crate::dispatching::FnHandler::auto_answer_callback_queries
+fn mark_answered<T, E>(
+    poll: Poll<Result<T, E>>,
+    answered: &Option<Arc<AtomicBool>>,
+) -> Poll<Result<T, E>> {
+    if let (Poll::Ready(Ok(_)), Some(answered)) = (&poll, answered) {
+        answered.store(true, Ordering::SeqCst);
+    }
+
+    poll
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teloxide_core::{requests::HasPayload, Bot};
+
+    use crate::{mock::user, utils::MessageBuilder};
+
+    fn callback_query() -> UpdateWithCx<Bot, CallbackQuery> {
+        let update = serde_json::from_value(serde_json::json!({
+            "id": "4382bfdwdsb323b2d9",
+            "from": user(1),
+            "chat_instance": "1",
+            "game_short_name": "tetris"
+        }))
+        .unwrap();
+
+        UpdateWithCx::new(Bot::new("Doesn't matter here"), update)
+    }
+
+    #[test]
+    fn answer_with_url() {
+        let request =
+            callback_query().answer_with_url(Url::parse("https://example.com/tetris").unwrap());
+
+        assert_eq!(
+            serde_json::to_value(request.payload_ref()).unwrap(),
+            serde_json::json!({
+                "callback_query_id": "4382bfdwdsb323b2d9",
+                "url": "https://example.com/tetris"
+            })
+        );
+    }
+
+    #[test]
+    fn answer() {
+        let request = callback_query().answer();
+
+        assert_eq!(
+            serde_json::to_value(request.payload_ref()).unwrap(),
+            serde_json::json!({ "callback_query_id": "4382bfdwdsb323b2d9" })
+        );
+    }
+
+    #[test]
+    fn answer_with_text() {
+        let request = callback_query().answer_with_text("Done", true);
+
+        assert_eq!(
+            serde_json::to_value(request.payload_ref()).unwrap(),
+            serde_json::json!({
+                "callback_query_id": "4382bfdwdsb323b2d9",
+                "text": "Done",
+                "show_alert": true
+            })
+        );
+    }
+
+    #[test]
+    fn data() {
+        assert_eq!(callback_query().data(), None);
+
+        let mut cx = callback_query();
+        cx.update.data = Some("like".to_owned());
+        assert_eq!(cx.data(), Some("like"));
+    }
+
+    #[test]
+    fn edit_message() {
+        let markup: InlineKeyboardMarkup = serde_json::from_value(serde_json::json!({
+            "inline_keyboard": [[{ "text": "Next", "callback_data": "2" }]]
+        }))
+        .unwrap();
+        assert!(callback_query().edit_message("Page 2", markup.clone()).is_none());
+
+        let mut cx = callback_query();
+        cx.update.message = Some(MessageBuilder::new().id(10).text("Page 1").chat(1).build());
+        let request = cx.edit_message("Page 2", markup).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(request.payload_ref()).unwrap(),
+            serde_json::json!({
+                "chat_id": 1,
+                "message_id": 10,
+                "text": "Page 2",
+                "reply_markup": {
+                    "inline_keyboard": [[{ "text": "Next", "callback_data": "2" }]]
+                }
+            })
+        );
+    }
+}
@@ -66,6 +66,8 @@ pub use dispatching::repls::{
 };
 
 mod logging;
+#[cfg(test)]
+mod mock;
 
 pub mod dispatching;
 pub mod error_handlers;
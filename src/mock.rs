@@ -0,0 +1,218 @@
+//! Test doubles shared by unit tests.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use serde_json::{json, Value};
+use teloxide_core::{
+    net::{self, Download},
+    types::{CallbackQuery, Update, UpdateKind},
+    Bot,
+};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use url::Url;
+
+use crate::utils::MessageBuilder;
+
+type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+type Respond = Arc<dyn Fn(&str, &Value) -> Value + Send + Sync>;
+
+/// A local HTTP server imitating the Telegram Bot API.
+///
+/// Every request is recorded and answered successfully with the result
+/// returned by `respond` for the name and the parameters of the called method.
+///
+/// Like Telegram, the server holds a `getUpdates` request with a `timeout`
+/// for that many seconds if there are no updates for it (i.e. `respond` has
+/// returned an empty array).
+pub(crate) struct MockApi {
+    url: Url,
+    requests: Requests,
+}
+
+impl MockApi {
+    pub(crate) async fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Value + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Requests::default();
+        let respond: Respond = Arc::new(respond);
+
+        tokio::spawn({
+            let requests = Arc::clone(&requests);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, Arc::clone(&requests), Arc::clone(&respond)));
+                }
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Returns a bot sending its requests to this server.
+    pub(crate) fn bot(&self) -> Bot {
+        let client = net::default_reqwest_settings().no_proxy().build().unwrap();
+        Bot::with_client("TOKEN", client).set_api_url(self.url.clone())
+    }
+
+    /// Returns the names and the parameters of the called methods, in the
+    /// order of the calls.
+    pub(crate) fn requests(&self) -> Vec<(String, Value)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(stream: TcpStream, requests: Requests, respond: Respond) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    loop {
+        // E.g. `POST /botTOKEN/getUpdates HTTP/1.1`.
+        let mut request_line = String::new();
+        if stream.read_line(&mut request_line).await? == 0 {
+            return Ok(());
+        }
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let method = path.rsplit('/').next().unwrap_or_default().to_owned();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            stream.read_line(&mut header).await?;
+
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        stream.read_exact(&mut body).await?;
+        let params = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+        let result = respond(&method, &params);
+        let long_poll = match params["timeout"].as_u64() {
+            Some(timeout) if method == "GetUpdates" && result == json!([]) => timeout,
+            _ => 0,
+        };
+        requests.lock().unwrap().push((method, params));
+
+        tokio::time::sleep(Duration::from_secs(long_poll)).await;
+
+        let body = serde_json::json!({ "ok": true, "result": result }).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.get_mut().write_all(response.as_bytes()).await?;
+    }
+}
+
+/// Returns the JSON of the user `id`.
+pub(crate) fn user(id: i64) -> Value {
+    json!({ "id": id, "is_bot": false, "first_name": "Hirrolot" })
+}
+
+/// Returns the update `id` with a text message from the user `1` in the private
+/// chat with them.
+pub(crate) fn message_update(id: i32, text: &str) -> Update {
+    let message = MessageBuilder::new().id(id).text(text).build();
+    Update { id, kind: UpdateKind::Message(message) }
+}
+
+/// Returns the callback query `id` from the user `1`.
+pub(crate) fn callback_query(id: i32) -> CallbackQuery {
+    serde_json::from_value(json!({
+        "id": id.to_string(),
+        "from": user(1),
+        "chat_instance": "1",
+        "data": "data"
+    }))
+    .unwrap()
+}
+
+/// Returns the update `id` with [`callback_query`] `id`.
+pub(crate) fn callback_query_update(id: i32) -> Update {
+    Update { id, kind: UpdateKind::CallbackQuery(callback_query(id)) }
+}
+
+/// Returns `updates` as Telegram sends them in response to `getUpdates`, so
+/// that they can be returned by [`MockApi`].
+pub(crate) fn updates_json(updates: &[Update]) -> Value {
+    updates.iter().map(|update| crate::dispatching::update_to_json(update).unwrap()).collect()
+}
+
+/// A downloader serving files from memory.
+///
+/// Files are streamed in the specified chunks, so that downloading can be
+/// observed chunk by chunk. Unknown paths fail with `404 Not Found`, like
+/// expired paths do on the Telegram side.
+#[derive(Default)]
+pub(crate) struct MockDownloader {
+    files: HashMap<&'static str, Vec<&'static [u8]>>,
+}
+
+impl MockDownloader {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves the file at `path` in `chunks`.
+    #[must_use]
+    pub(crate) fn file(mut self, path: &'static str, chunks: Vec<&'static [u8]>) -> Self {
+        self.files.insert(path, chunks);
+        self
+    }
+}
+
+impl<'w> Download<'w> for MockDownloader {
+    type Err = &'static str;
+    type Fut = BoxFuture<'w, Result<(), &'static str>>;
+
+    fn download_file(
+        &self,
+        path: &str,
+        destination: &'w mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Self::Fut {
+        let mut stream = self.download_file_stream(path);
+
+        Box::pin(async move {
+            while let Some(chunk) = stream.next().await {
+                destination.write_all(&chunk?).await.map_err(|_| "Failed to write")?;
+            }
+            Ok(())
+        })
+    }
+
+    type StreamErr = &'static str;
+    type Stream = BoxStream<'static, Result<Bytes, &'static str>>;
+
+    fn download_file_stream(&self, path: &str) -> Self::Stream {
+        let chunks: Vec<_> = match self.files.get(path) {
+            Some(chunks) => chunks.iter().map(|chunk| Ok(Bytes::from_static(chunk))).collect(),
+            None => vec![Err("404 Not Found")],
+        };
+        Box::pin(stream::iter(chunks))
+    }
+}
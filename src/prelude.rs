@@ -3,13 +3,14 @@
 pub use crate::{
     dispatching::{
         dialogue::{
-            exit, next, DialogueDispatcher, DialogueStage, DialogueWithCx, GetChatId, Transition,
-            TransitionIn, TransitionOut,
+            exit, next, redispatch, DialogueDispatcher, DialogueStage, DialogueWithCx, GetChatId,
+            Transition, TransitionIn, TransitionOut, TransitionOutExt,
         },
-        Dispatcher, DispatcherHandlerRx, DispatcherHandlerRxExt, UpdateWithCx,
+        Dispatcher, DispatcherHandlerRx, DispatcherHandlerRxExt, UpdateExt, UpdateWithCx,
     },
     error_handlers::{LoggingErrorHandler, OnError},
     respond,
+    utils::RequestExt,
 };
 
 #[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "macros")))]
@@ -0,0 +1,138 @@
+use std::fmt::{Debug, Display};
+
+use teloxide_core::{
+    requests::{Request, Requester},
+    types::ChatMember,
+};
+use thiserror::Error;
+
+/// An error returned from [`require_admin`].
+#[derive(Debug, Error)]
+pub enum RequireAdminError<E>
+where
+    E: Debug + Display,
+{
+    /// The user is neither an administrator nor the owner of the chat.
+    #[error("the user {user_id} is not an administrator of the chat {chat_id}")]
+    NotAdmin { chat_id: i64, user_id: i64 },
+
+    /// Returned from [`GetChatMember`].
+    ///
+    /// [`GetChatMember`]: crate::payloads::GetChatMember
+    #[error("failed to get the chat member: {0}")]
+    GetChatMember(E),
+}
+
+/// Returns `true` if the user is an administrator or the owner of the chat.
+///
+/// See also: [`ChatMemberCache`], which doesn't request the same members over
+/// and over again.
+///
+/// [`ChatMemberCache`]: crate::utils::ChatMemberCache
+pub async fn is_chat_admin<R>(requester: &R, chat_id: i64, user_id: i64) -> Result<bool, R::Err>
+where
+    R: Requester,
+{
+    let member = requester.get_chat_member(chat_id, user_id).send().await?;
+    Ok(is_admin(&member))
+}
+
+/// Returns an error if the user is neither an administrator nor the owner of
+/// the chat.
+pub async fn require_admin<R>(
+    requester: &R,
+    chat_id: i64,
+    user_id: i64,
+) -> Result<(), RequireAdminError<R::Err>>
+where
+    R: Requester,
+    R::Err: Debug + Display,
+{
+    let is_admin = is_chat_admin(requester, chat_id, user_id)
+        .await
+        .map_err(RequireAdminError::GetChatMember)?;
+
+    if is_admin {
+        Ok(())
+    } else {
+        Err(RequireAdminError::NotAdmin { chat_id, user_id })
+    }
+}
+
+fn is_admin(member: &ChatMember) -> bool {
+    member.kind.is_privileged()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{user, MockApi};
+
+    fn member_json(status: &str) -> serde_json::Value {
+        let mut member = serde_json::json!({
+            "user": user(42),
+            "status": status,
+            "is_anonymous": false
+        });
+
+        if status == "administrator" {
+            let permissions = serde_json::json!({
+                "can_be_edited": false,
+                "can_manage_chat": true,
+                "can_change_info": false,
+                "can_delete_messages": true,
+                "can_manage_voice_chats": false,
+                "can_invite_users": true,
+                "can_restrict_members": true,
+                "can_promote_members": false
+            });
+            member.as_object_mut().unwrap().extend(permissions.as_object().unwrap().clone());
+        }
+
+        member
+    }
+
+    fn member(status: &str) -> ChatMember {
+        serde_json::from_value(member_json(status)).unwrap()
+    }
+
+    #[test]
+    fn owner_is_admin() {
+        assert!(is_admin(&member("creator")));
+    }
+
+    #[test]
+    fn administrator_is_admin() {
+        assert!(is_admin(&member("administrator")));
+    }
+
+    #[test]
+    fn member_is_not_admin() {
+        assert!(!is_admin(&member("member")));
+    }
+
+    #[tokio::test]
+    async fn chat_member_is_requested() {
+        // The user #1 is an administrator, the others are plain members.
+        let api = MockApi::start(|_, params| match params["user_id"].as_i64() {
+            Some(1) => member_json("administrator"),
+            _ => member_json("member"),
+        })
+        .await;
+        let bot = api.bot();
+
+        assert!(is_chat_admin(&bot, -100, 1).await.unwrap());
+        assert!(!is_chat_admin(&bot, -100, 2).await.unwrap());
+
+        assert!(require_admin(&bot, -100, 1).await.is_ok());
+        assert!(matches!(
+            require_admin(&bot, -100, 2).await,
+            Err(RequireAdminError::NotAdmin { chat_id: -100, user_id: 2 })
+        ));
+
+        let params = serde_json::json!({ "chat_id": -100, "user_id": 1 });
+        assert_eq!(api.requests()[0], ("GetChatMember".to_owned(), params));
+        assert_eq!(api.requests().len(), 4);
+    }
+}
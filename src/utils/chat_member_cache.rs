@@ -0,0 +1,168 @@
+use std::{future::Future, time::Duration};
+
+use teloxide_core::{
+    requests::{Request, Requester},
+    types::{ChatMember, ChatMemberUpdated},
+};
+
+use crate::utils::ttl_cache::TtlCache;
+
+/// A cache of [`GetChatMember`] results.
+///
+/// Permission checks often request the same chat members over and over again.
+/// This cache memoizes the members for `ttl` and holds at most `capacity` of
+/// them: when it's full, expired entries are dropped first, then the oldest
+/// ones.
+///
+/// To keep the cache consistent with changes of members, pass
+/// [`ChatMemberUpdated`] updates (see [`Dispatcher::chat_members_handler`])
+/// into [`ChatMemberCache::handle_update`].
+///
+/// Note that this is a standalone cache rather than a requester adaptor (there
+/// is no `Bot::with_member_cache`): only the members requested via
+/// [`ChatMemberCache::get_chat_member`] are cached, while `getChatMember`
+/// requests made directly through a requester bypass the cache. It isn't wired
+/// into [`Dispatcher`] either, so updates of members have to be passed into
+/// [`ChatMemberCache::handle_update`] by your own handler.
+///
+/// [`GetChatMember`]: crate::payloads::GetChatMember
+/// [`Dispatcher`]: crate::dispatching::Dispatcher
+/// [`Dispatcher::chat_members_handler`]: crate::dispatching::Dispatcher::chat_members_handler
+pub struct ChatMemberCache {
+    members: TtlCache<(i64, i64), ChatMember>,
+}
+
+impl ChatMemberCache {
+    /// Creates a cache which holds at most `capacity` members for `ttl` each.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self { members: TtlCache::new(ttl, capacity) }
+    }
+
+    /// Returns the member of the chat, requesting it only if it isn't cached.
+    pub async fn get_chat_member<R>(
+        &self,
+        requester: &R,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<ChatMember, R::Err>
+    where
+        R: Requester,
+    {
+        self.get_or_fetch(chat_id, user_id, || requester.get_chat_member(chat_id, user_id).send())
+            .await
+    }
+
+    /// Returns the member of the chat, calling `fetch` only if it isn't cached.
+    pub async fn get_or_fetch<F, Fut, E>(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        fetch: F,
+    ) -> Result<ChatMember, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ChatMember, E>>,
+    {
+        self.members.get_or_fetch((chat_id, user_id), fetch).await
+    }
+
+    /// Updates the cached member from [`ChatMemberUpdated`].
+    pub fn handle_update(&self, update: &ChatMemberUpdated) {
+        let member = update.new_chat_member.clone();
+        self.members.insert((update.chat.id, member.user.id), member);
+    }
+
+    /// Removes the member of the chat from the cache.
+    pub fn invalidate(&self, chat_id: i64, user_id: i64) {
+        self.members.remove(&(chat_id, user_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::mock::{user, MockApi};
+
+    fn member(user_id: i64) -> ChatMember {
+        serde_json::from_value(serde_json::json!({
+            "user": user(user_id),
+            "status": "member"
+        }))
+        .unwrap()
+    }
+
+    async fn get(cache: &ChatMemberCache, fetches: &AtomicUsize, user_id: i64) -> ChatMember {
+        cache
+            .get_or_fetch(1, user_id, || async {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, Infallible>(member(user_id))
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetches_once_within_ttl() {
+        let cache = ChatMemberCache::new(Duration::from_secs(60), 10);
+        let fetches = AtomicUsize::new(0);
+
+        assert_eq!(get(&cache, &fetches, 42).await.user.id, 42);
+        assert_eq!(get(&cache, &fetches, 42).await.user.id, 42);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn requests_once_within_ttl() {
+        let api = MockApi::start(|_, params| {
+            serde_json::json!({
+                "user": user(params["user_id"].as_i64().unwrap()),
+                "status": "member"
+            })
+        })
+        .await;
+        let bot = api.bot();
+        let cache = ChatMemberCache::new(Duration::from_secs(60), 10);
+
+        assert_eq!(cache.get_chat_member(&bot, 1, 42).await.unwrap().user.id, 42);
+        assert_eq!(cache.get_chat_member(&bot, 1, 42).await.unwrap().user.id, 42);
+
+        let params = serde_json::json!({ "chat_id": 1, "user_id": 42 });
+        assert_eq!(api.requests(), vec![("GetChatMember".to_owned(), params)]);
+    }
+
+    #[tokio::test]
+    async fn fetches_again_after_ttl() {
+        let cache = ChatMemberCache::new(Duration::from_millis(50), 10);
+        let fetches = AtomicUsize::new(0);
+
+        get(&cache, &fetches, 42).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        get(&cache, &fetches, 42).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn is_bounded() {
+        let cache = ChatMemberCache::new(Duration::from_secs(60), 2);
+        let fetches = AtomicUsize::new(0);
+
+        for user_id in 0..5 {
+            get(&cache, &fetches, user_id).await;
+        }
+        assert_eq!(cache.members.len(), 2);
+
+        // The most recent members are kept.
+        get(&cache, &fetches, 4).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 5);
+    }
+}
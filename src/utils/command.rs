@@ -32,6 +32,15 @@
 //! assert_eq!(args, vec!["3", "hours"]);
 //! ```
 //!
+//! # Using split_command
+//! ```
+//! use teloxide::utils::command::split_command;
+//!
+//! let (command, rest) = split_command("/echo@MyBotName Hello, world!", "MyBotName").unwrap();
+//! assert_eq!(command, "echo");
+//! assert_eq!(rest, "Hello, world!");
+//! ```
+//!
 //! # Using parse_command_with_prefix
 //! ```
 //! use teloxide::utils::command::parse_command_with_prefix;
@@ -78,20 +87,20 @@ pub use teloxide_macros::BotCommand;
 ///
 /// # Enum attributes
 ///  1. `#[command(rename = "rule")]`
-/// Rename all commands by `rule`. Allowed rules are `lowercase`. If you will
-/// not use this attribute, commands will be parsed by their original names.
+///     Rename all commands by `rule`. Allowed rules are `lowercase`. If you will
+///     not use this attribute, commands will be parsed by their original names.
 ///
 ///  2. `#[command(prefix = "prefix")]`
-/// Change a prefix for all commands (the default is `/`).
+///     Change a prefix for all commands (the default is `/`).
 ///
 ///  3. `#[command(description = "description")]`
-/// Add a sumary description of commands before all commands.
+///     Add a sumary description of commands before all commands.
 ///
 ///  4. `#[command(parse_with = "parser")]`
-/// Change the parser of arguments. Possible values:
+///     Change the parser of arguments. Possible values:
 ///    - `default` - the same as the unspecified parser. It only puts all text
-///    after the first space into the first argument, which must implement
-///    [`FromStr`].
+///      after the first space into the first argument, which must implement
+///      [`FromStr`].
 ///
 /// ## Example
 /// ```
@@ -130,8 +139,8 @@ pub use teloxide_macros::BotCommand;
 /// ```
 ///
 /// 5. `#[command(separator = "sep")]`
-/// Specify separator used by the `split` parser. It will be ignored when
-/// accompanied by another type of parsers.
+///    Specify separator used by the `split` parser. It will be ignored when
+///    accompanied by another type of parsers.
 ///
 /// ## Example
 /// ```
@@ -153,18 +162,18 @@ pub use teloxide_macros::BotCommand;
 /// All variant attributes override the corresponding `enum` attributes.
 ///
 ///  1. `#[command(rename = "rule")]`
-/// Rename one command by a rule. Allowed rules are `lowercase`, `%some_name%`,
-/// where `%some_name%` is any string, a new name.
+///     Rename one command by a rule. Allowed rules are `lowercase`, `%some_name%`,
+///     where `%some_name%` is any string, a new name.
 ///
 ///  2. `#[command(description = "description")]`
-/// Give your command a description. Write `"off"` for `"description"` to hide a
-/// command.
+///     Give your command a description. Write `"off"` for `"description"` to hide a
+///     command.
 ///
 ///  3. `#[command(parse_with = "parser")]`
-/// One more option is available for variants.
+///     One more option is available for variants.
 ///    - `custom_parser` - your own parser of the signature `fn(String) ->
-///    Result<Tuple, ParseError>`, where `Tuple` corresponds to the variant's
-/// arguments.
+///      Result<Tuple, ParseError>`, where `Tuple` corresponds to the variant's
+///      arguments.
 ///
 /// ## Example
 /// ```
@@ -340,6 +349,47 @@ where
     Some((command, words.collect()))
 }
 
+/// Splits a string into a command and the rest of the string.
+///
+/// Unlike [`parse_command`], the arguments are not split, so this function is
+/// useful for commands with free-form arguments. The command is returned
+/// without the leading `/` and the `@bot_name` suffix, and the rest is returned
+/// without the leading whitespace.
+///
+/// ## Example
+/// ```
+/// use teloxide::utils::command::split_command;
+///
+/// let text = "/echo@my_bot  Hello,   world!";
+/// assert_eq!(split_command(text, "my_bot"), Some(("echo", "Hello,   world!")));
+/// ```
+///
+/// If the command is addressed to another bot, it will return `None`:
+/// ```
+/// use teloxide::utils::command::split_command;
+///
+/// assert_eq!(split_command("/echo@other_bot Hello", "my_bot"), None);
+/// ```
+///
+/// [`parse_command`]: crate::utils::command::parse_command
+pub fn split_command<N>(text: &str, bot_name: N) -> Option<(&str, &str)>
+where
+    N: AsRef<str>,
+{
+    let text = text.strip_prefix('/')?;
+    let (head, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim_start()),
+        None => (text, ""),
+    };
+
+    let mut splited = head.splitn(2, '@');
+    let command = splited.next().filter(|command| !command.is_empty())?;
+    match splited.next() {
+        Some(name) if name != bot_name.as_ref() => None,
+        _ => Some((command, rest)),
+    }
+}
+
 // The rest of tests are integrational due to problems with macro expansion in
 // unit tests.
 #[cfg(test)]
@@ -361,4 +411,14 @@ mod tests {
         let actual = parse_command(data, "");
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn split_command_() {
+        assert_eq!(split_command("/start", "mybot"), Some(("start", "")));
+        assert_eq!(split_command("/start@mybot arg", "mybot"), Some(("start", "arg")));
+        assert_eq!(split_command("/start@otherbot arg", "mybot"), None);
+        assert_eq!(split_command("/echo a  b\nc", ""), Some(("echo", "a  b\nc")));
+        assert_eq!(split_command("start", "mybot"), None);
+        assert_eq!(split_command("/ start", "mybot"), None);
+    }
 }
@@ -0,0 +1,324 @@
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+};
+
+use futures::StreamExt;
+use teloxide_core::{
+    net::Download,
+    requests::{Request, Requester},
+    types::File,
+};
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// An error returned from [`download_file_limited`].
+#[derive(Debug, Error)]
+pub enum LimitedDownloadError<E>
+where
+    E: Debug + Display,
+{
+    /// The file exceeds the limit, either according to [`File::file_size`] or
+    /// to the number of actually received bytes.
+    ///
+    /// [`File::file_size`]: crate::types::File::file_size
+    #[error("the file is too large: at least {size} bytes, while the limit is {max_bytes} bytes")]
+    TooLarge { size: u64, max_bytes: u64 },
+
+    #[error("a network error: {0}")]
+    Network(E),
+
+    #[error("an I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Downloads `file` into `destination`, unless it's larger than `max_bytes`.
+///
+/// The size of the file is checked before the downloading, using
+/// [`File::file_size`] returned from [`GetFile`]. Since the size might be
+/// misreported, the limit is also enforced while downloading: as soon as more
+/// than `max_bytes` bytes are received, the downloading is aborted. In both
+/// cases [`LimitedDownloadError::TooLarge`] is returned.
+///
+/// Note that on abortion `destination` may already contain up to `max_bytes`
+/// bytes of the file.
+///
+/// [`File::file_size`]: crate::types::File::file_size
+/// [`GetFile`]: crate::payloads::GetFile
+pub async fn download_file_limited<'w, D>(
+    downloader: &D,
+    file: &File,
+    max_bytes: u64,
+    destination: &mut (dyn AsyncWrite + Unpin + Send),
+) -> Result<(), LimitedDownloadError<D::StreamErr>>
+where
+    D: Download<'w>,
+    D::StreamErr: Debug + Display,
+{
+    let declared = u64::from(file.file_size);
+    if declared > max_bytes {
+        return Err(LimitedDownloadError::TooLarge { size: declared, max_bytes });
+    }
+
+    let mut stream = Box::pin(downloader.download_file_stream(&file.file_path));
+    let mut received = 0u64;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(LimitedDownloadError::Network)?;
+
+        received += chunk.len() as u64;
+        if received > max_bytes {
+            return Err(LimitedDownloadError::TooLarge { size: received, max_bytes });
+        }
+
+        destination.write_all(&chunk).await?;
+    }
+
+    destination.flush().await?;
+    Ok(())
+}
+
+/// An error returned from [`download_by_file_id`].
+#[derive(Debug, Error)]
+pub enum DownloadByFileIdError<RE, DE>
+where
+    RE: Debug + Display,
+    DE: Debug + Display,
+{
+    /// Returned from [`GetFile`].
+    ///
+    /// [`GetFile`]: crate::payloads::GetFile
+    #[error("failed to get the file: {0}")]
+    GetFile(RE),
+
+    #[error("a network error: {0}")]
+    Network(DE),
+
+    #[error("an I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Downloads the file with `file_id` into `destination`.
+///
+/// This is a shortcut for [`GetFile`] followed by downloading of the file by
+/// its path. Returns the file metadata returned from [`GetFile`].
+///
+/// See also: [`FilePathCache::download_by_file_id`], which doesn't request the
+/// path of a recently downloaded file once again.
+///
+/// [`GetFile`]: crate::payloads::GetFile
+/// [`FilePathCache::download_by_file_id`]: crate::utils::FilePathCache::download_by_file_id
+pub async fn download_by_file_id<'w, R>(
+    requester: &R,
+    file_id: &str,
+    destination: &mut (dyn AsyncWrite + Unpin + Send),
+) -> Result<File, DownloadByFileIdError<<R as Requester>::Err, R::StreamErr>>
+where
+    R: Requester + Download<'w>,
+    <R as Requester>::Err: Debug + Display,
+    R::StreamErr: Debug + Display,
+{
+    download_fetched(requester, requester.get_file(file_id).send(), destination).await
+}
+
+/// Downloads the file returned from `get_file` into `destination`.
+pub(crate) async fn download_fetched<'w, D, Fut, E>(
+    downloader: &D,
+    get_file: Fut,
+    destination: &mut (dyn AsyncWrite + Unpin + Send),
+) -> Result<File, DownloadByFileIdError<E, D::StreamErr>>
+where
+    D: Download<'w>,
+    D::StreamErr: Debug + Display,
+    Fut: Future<Output = Result<File, E>>,
+    E: Debug + Display,
+{
+    let file = get_file.await.map_err(DownloadByFileIdError::GetFile)?;
+    download_path(downloader, &file.file_path, destination).await?;
+    Ok(file)
+}
+
+/// An error returned from [`download_path`].
+pub(crate) enum DownloadPathError<E> {
+    /// `received` is the number of bytes written into the destination before
+    /// the error.
+    Network {
+        error: E,
+        received: u64,
+    },
+    Io(std::io::Error),
+}
+
+impl<RE, DE> From<DownloadPathError<DE>> for DownloadByFileIdError<RE, DE>
+where
+    RE: Debug + Display,
+    DE: Debug + Display,
+{
+    fn from(error: DownloadPathError<DE>) -> Self {
+        match error {
+            DownloadPathError::Network { error, .. } => Self::Network(error),
+            DownloadPathError::Io(error) => Self::Io(error),
+        }
+    }
+}
+
+/// Downloads the file with `path` into `destination`.
+pub(crate) async fn download_path<'w, D>(
+    downloader: &D,
+    path: &str,
+    destination: &mut (dyn AsyncWrite + Unpin + Send),
+) -> Result<(), DownloadPathError<D::StreamErr>>
+where
+    D: Download<'w>,
+{
+    let mut stream = Box::pin(downloader.download_file_stream(path));
+    let mut received = 0u64;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|error| DownloadPathError::Network { error, received })?;
+        destination.write_all(&chunk).await.map_err(DownloadPathError::Io)?;
+        received += chunk.len() as u64;
+    }
+
+    destination.flush().await.map_err(DownloadPathError::Io)
+}
+
+/// An error returned from [`download_file_with_progress`].
+#[derive(Debug, Error)]
+pub enum DownloadStreamError<E>
+where
+    E: Debug + Display,
+{
+    #[error("a network error: {0}")]
+    Network(E),
+
+    #[error("an I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Downloads `file` into `destination`, reporting the progress to
+/// `on_progress`.
+///
+/// `on_progress` is called after each received chunk with the number of bytes
+/// received so far and the total size of the file, if it's known from
+/// [`File::file_size`].
+///
+/// [`File::file_size`]: crate::types::File::file_size
+pub async fn download_file_with_progress<'w, D, F>(
+    downloader: &D,
+    file: &File,
+    destination: &mut (dyn AsyncWrite + Unpin + Send),
+    mut on_progress: F,
+) -> Result<(), DownloadStreamError<D::StreamErr>>
+where
+    D: Download<'w>,
+    D::StreamErr: Debug + Display,
+    F: FnMut(u64, Option<u64>),
+{
+    let total = Some(u64::from(file.file_size)).filter(|&size| size != 0);
+
+    let mut stream = Box::pin(downloader.download_file_stream(&file.file_path));
+    let mut received = 0u64;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(DownloadStreamError::Network)?;
+        destination.write_all(&chunk).await?;
+
+        received += chunk.len() as u64;
+        on_progress(received, total);
+    }
+
+    destination.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    use crate::mock::MockDownloader;
+
+    /// Serves `chunks` as the file returned by [`file`].
+    fn downloader(chunks: Vec<&'static [u8]>) -> MockDownloader {
+        MockDownloader::new().file("photos/file_0.jpg", chunks)
+    }
+
+    fn file(file_size: u32) -> File {
+        serde_json::from_value(serde_json::json!({
+            "file_id": "id",
+            "file_unique_id": "unique_id",
+            "file_size": file_size,
+            "file_path": "photos/file_0.jpg",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn downloads_small_file() {
+        let downloader = downloader(vec![b"abc", b"def"]);
+        let mut destination = Vec::new();
+
+        download_file_limited(&downloader, &file(6), 6, &mut destination).await.unwrap();
+        assert_eq!(destination, b"abcdef");
+    }
+
+    #[tokio::test]
+    async fn rejects_declared_large_file() {
+        let downloader = downloader(vec![b"abc"]);
+        let mut destination = Vec::new();
+
+        let res = download_file_limited(&downloader, &file(100), 10, &mut destination).await;
+        assert!(matches!(res, Err(LimitedDownloadError::TooLarge { size: 100, max_bytes: 10 })));
+        assert!(destination.is_empty());
+    }
+
+    #[tokio::test]
+    async fn aborts_misreported_large_file() {
+        let downloader = downloader(vec![b"abc", b"def", b"ghi"]);
+        let mut destination = Vec::new();
+
+        let res = download_file_limited(&downloader, &file(3), 5, &mut destination).await;
+        assert!(matches!(res, Err(LimitedDownloadError::TooLarge { size: 6, max_bytes: 5 })));
+        assert_eq!(destination, b"abc");
+    }
+
+    #[tokio::test]
+    async fn downloads_fetched_file() {
+        let downloader = downloader(vec![b"abc", b"def"]);
+        let mut destination = Vec::new();
+
+        let get_file = async { Ok::<_, Infallible>(file(6)) };
+        let file = download_fetched(&downloader, get_file, &mut destination).await.unwrap();
+        assert_eq!(file.file_path, "photos/file_0.jpg");
+        assert_eq!(destination, b"abcdef");
+    }
+
+    #[tokio::test]
+    async fn doesnt_download_if_get_file_fails() {
+        let downloader = downloader(vec![b"abc"]);
+        let mut destination = Vec::new();
+
+        let get_file = async { Err::<File, _>("Oh no") };
+        let res = download_fetched(&downloader, get_file, &mut destination).await;
+        assert!(matches!(res, Err(DownloadByFileIdError::GetFile("Oh no"))));
+        assert!(destination.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_progress() {
+        let downloader = downloader(vec![b"abc", b"de", b"f"]);
+        let mut destination = Vec::new();
+        let mut progress = Vec::new();
+
+        download_file_with_progress(&downloader, &file(6), &mut destination, |received, total| {
+            progress.push((received, total))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(progress, vec![(3, Some(6)), (5, Some(6)), (6, Some(6))]);
+        assert_eq!(destination, b"abcdef");
+    }
+}
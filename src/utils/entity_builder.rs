@@ -0,0 +1,119 @@
+use teloxide_core::types::{MessageEntity, MessageEntityKind};
+
+use crate::utils::text::utf16_len;
+
+/// A builder of a text with explicit [`MessageEntity`]s.
+///
+/// Unlike [`html`] and [`markdown`], this doesn't require a parse mode: the
+/// text and its entities are passed as is, e.g. into
+/// [`SendMessage::entities`]. Offsets and lengths of the entities are computed
+/// in [UTF-16 code units], as Telegram requires.
+///
+/// ## Examples
+/// ```
+/// use teloxide::utils::EntityBuilder;
+///
+/// let (text, entities) =
+///     EntityBuilder::new().text("Read ").link("the docs", "https://docs.rs/teloxide").build();
+///
+/// assert_eq!(text, "Read the docs");
+/// assert_eq!((entities[0].offset, entities[0].length), (5, 8));
+/// ```
+///
+/// [`html`]: crate::utils::html
+/// [`markdown`]: crate::utils::markdown
+/// [`SendMessage::entities`]: crate::payloads::SendMessage::entities
+/// [UTF-16 code units]: https://core.telegram.org/api/entities#entity-length
+#[derive(Clone, Debug, Default)]
+pub struct EntityBuilder {
+    text: String,
+    entities: Vec<MessageEntity>,
+}
+
+impl EntityBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a plain text.
+    #[must_use]
+    pub fn text(mut self, s: &str) -> Self {
+        self.text.push_str(s);
+        self
+    }
+
+    /// Appends a bold text.
+    #[must_use]
+    pub fn bold(self, s: &str) -> Self {
+        self.styled(s, MessageEntityKind::Bold)
+    }
+
+    /// Appends an italic text.
+    #[must_use]
+    pub fn italic(self, s: &str) -> Self {
+        self.styled(s, MessageEntityKind::Italic)
+    }
+
+    /// Appends a monowidth text.
+    #[must_use]
+    pub fn code(self, s: &str) -> Self {
+        self.styled(s, MessageEntityKind::Code)
+    }
+
+    /// Appends a text which links to `url`.
+    #[must_use]
+    pub fn link<U>(self, s: &str, url: U) -> Self
+    where
+        U: Into<String>,
+    {
+        self.styled(s, MessageEntityKind::TextLink { url: url.into() })
+    }
+
+    /// Appends a text with an entity of `kind`.
+    ///
+    /// An empty text is appended without an entity, since Telegram rejects
+    /// empty entities.
+    #[must_use]
+    pub fn styled(mut self, s: &str, kind: MessageEntityKind) -> Self {
+        let length = utf16_len(s);
+        if length != 0 {
+            let offset = utf16_len(&self.text);
+            self.entities.push(MessageEntity { kind, offset, length });
+        }
+
+        self.text(s)
+    }
+
+    /// Returns the text and its entities.
+    #[must_use]
+    pub fn build(self) -> (String, Vec<MessageEntity>) {
+        (self.text, self.entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_are_in_utf16() {
+        let (text, entities) = EntityBuilder::new()
+            .text("🦀 says ")
+            .bold("привет")
+            .text(", ")
+            .code("x")
+            .bold("")
+            .build();
+
+        assert_eq!(text, "🦀 says привет, x");
+        assert_eq!(
+            entities,
+            vec![
+                // The crab is 2 UTF-16 code units long.
+                MessageEntity { kind: MessageEntityKind::Bold, offset: 8, length: 6 },
+                MessageEntity { kind: MessageEntityKind::Code, offset: 16, length: 1 },
+            ]
+        );
+    }
+}
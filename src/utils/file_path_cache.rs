@@ -0,0 +1,244 @@
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    time::Duration,
+};
+
+use teloxide_core::{
+    net::Download,
+    requests::{Request, Requester},
+    types::File,
+};
+use tokio::io::AsyncWrite;
+
+use crate::utils::{
+    download::{download_fetched, download_path, DownloadPathError},
+    ttl_cache::TtlCache,
+    DownloadByFileIdError,
+};
+
+/// A cache of [`GetFile`] results.
+///
+/// A file path returned by [`GetFile`] is valid for at least an hour, so
+/// there is no need to request it again to download the same file several
+/// times. This cache memoizes the files (including their paths) for `ttl` and
+/// holds at most `capacity` of them: when it's full, expired entries are
+/// dropped first, then the oldest ones.
+///
+/// ## Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use teloxide::{net::Download, utils::FilePathCache, Bot};
+///
+/// # async fn run(file_id: String) -> Result<(), Box<dyn std::error::Error>> {
+/// let bot = Bot::new("TOKEN");
+/// let cache = FilePathCache::new(Duration::from_secs(60 * 60), 1024);
+///
+/// let file = cache.get_file(&bot, &file_id).await?;
+/// let mut dst = tokio::fs::File::create("/tmp/file").await?;
+/// bot.download_file(&file.file_path, &mut dst).await?;
+/// # Ok(()) }
+/// ```
+///
+/// [`GetFile`]: crate::payloads::GetFile
+pub struct FilePathCache {
+    files: TtlCache<String, File>,
+}
+
+impl FilePathCache {
+    /// Creates a cache which holds at most `capacity` files for `ttl` each.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self { files: TtlCache::new(ttl, capacity) }
+    }
+
+    /// Returns the file with `file_id`, requesting it only if it isn't cached.
+    pub async fn get_file<R>(&self, requester: &R, file_id: &str) -> Result<File, R::Err>
+    where
+        R: Requester,
+    {
+        self.get_or_fetch(file_id, || requester.get_file(file_id).send()).await
+    }
+
+    /// Returns the file with `file_id`, calling `fetch` only if it isn't
+    /// cached.
+    pub async fn get_or_fetch<F, Fut, E>(&self, file_id: &str, fetch: F) -> Result<File, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<File, E>>,
+    {
+        self.files.get_or_fetch(file_id.to_owned(), fetch).await
+    }
+
+    /// Downloads the file with `file_id` into `destination`, requesting its
+    /// path only if it isn't cached.
+    ///
+    /// See also: [`download_by_file_id`].
+    ///
+    /// [`download_by_file_id`]: crate::utils::download_by_file_id
+    pub async fn download_by_file_id<'w, R>(
+        &self,
+        requester: &R,
+        file_id: &str,
+        destination: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<File, DownloadByFileIdError<<R as Requester>::Err, R::StreamErr>>
+    where
+        R: Requester + Download<'w>,
+        <R as Requester>::Err: Debug + Display,
+        R::StreamErr: Debug + Display,
+    {
+        download_fetched(requester, self.get_file(requester, file_id), destination).await
+    }
+
+    /// Like [`FilePathCache::download_by_file_id`], but refreshes an expired
+    /// path.
+    ///
+    /// A cached path may expire before `ttl`, and then the download fails
+    /// (e.g. with `404 Not Found`). If `is_expired` returns `true` for the
+    /// download error and nothing has been written into `destination` yet,
+    /// the path is requested again and the download is retried once.
+    pub async fn download_by_file_id_or_refresh<'w, R, F>(
+        &self,
+        requester: &R,
+        file_id: &str,
+        destination: &mut (dyn AsyncWrite + Unpin + Send),
+        is_expired: F,
+    ) -> Result<File, DownloadByFileIdError<<R as Requester>::Err, R::StreamErr>>
+    where
+        R: Requester + Download<'w>,
+        <R as Requester>::Err: Debug + Display,
+        R::StreamErr: Debug + Display,
+        F: Fn(&R::StreamErr) -> bool,
+    {
+        let fetch = || requester.get_file(file_id).send();
+        self.download_or_refresh(requester, file_id, fetch, destination, is_expired).await
+    }
+
+    async fn download_or_refresh<'w, D, F, Fut, E, P>(
+        &self,
+        downloader: &D,
+        file_id: &str,
+        fetch: F,
+        destination: &mut (dyn AsyncWrite + Unpin + Send),
+        is_expired: P,
+    ) -> Result<File, DownloadByFileIdError<E, D::StreamErr>>
+    where
+        D: Download<'w>,
+        D::StreamErr: Debug + Display,
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<File, E>>,
+        E: Debug + Display,
+        P: Fn(&D::StreamErr) -> bool,
+    {
+        let file =
+            self.get_or_fetch(file_id, &fetch).await.map_err(DownloadByFileIdError::GetFile)?;
+
+        match download_path(downloader, &file.file_path, destination).await {
+            Err(DownloadPathError::Network { error, received: 0 }) if is_expired(&error) => {
+                log::debug!("The path of the file {} has expired, requesting a new one", file_id);
+
+                self.invalidate(file_id);
+                download_fetched(downloader, self.get_or_fetch(file_id, &fetch), destination).await
+            }
+            res => res.map(|()| file).map_err(Into::into),
+        }
+    }
+
+    /// Removes the file with `file_id` from the cache.
+    pub fn invalidate(&self, file_id: &str) {
+        self.files.remove(&file_id.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::mock::MockDownloader;
+
+    fn file(file_id: &str, file_path: &str) -> File {
+        serde_json::from_value(serde_json::json!({
+            "file_id": file_id,
+            "file_unique_id": "unique_id",
+            "file_size": 1024,
+            "file_path": file_path
+        }))
+        .unwrap()
+    }
+
+    async fn get(cache: &FilePathCache, fetches: &AtomicUsize, file_id: &str) -> File {
+        cache
+            .get_or_fetch(file_id, || async {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, Infallible>(file(file_id, &format!("documents/{}", file_id)))
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_file_once_within_ttl() {
+        let cache = FilePathCache::new(Duration::from_secs(60), 10);
+        let fetches = AtomicUsize::new(0);
+
+        assert_eq!(get(&cache, &fetches, "id").await.file_path, "documents/id");
+        assert_eq!(get(&cache, &fetches, "id").await.file_path, "documents/id");
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        cache.invalidate("id");
+        get(&cache, &fetches, "id").await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    async fn download(
+        cache: &FilePathCache,
+        fetches: &AtomicUsize,
+        is_expired: fn(&&'static str) -> bool,
+    ) -> (Result<File, DownloadByFileIdError<Infallible, &'static str>>, Vec<u8>) {
+        // The old path has expired, so it isn't served anymore.
+        let downloader = MockDownloader::new().file("documents/new", vec![b"abc"]);
+        let fetch = move || async move {
+            // The first path is expired by the time it's downloaded.
+            let path = match fetches.fetch_add(1, Ordering::SeqCst) {
+                0 => "documents/old",
+                _ => "documents/new",
+            };
+            Ok::<_, Infallible>(file("id", path))
+        };
+
+        let mut destination = Vec::new();
+        let res =
+            cache.download_or_refresh(&downloader, "id", fetch, &mut destination, is_expired).await;
+        (res, destination)
+    }
+
+    #[tokio::test]
+    async fn expired_path_is_refreshed() {
+        let cache = FilePathCache::new(Duration::from_secs(60), 10);
+        let fetches = AtomicUsize::new(0);
+
+        let (res, destination) = download(&cache, &fetches, |_| true).await;
+        assert_eq!(res.unwrap().file_path, "documents/new");
+        assert_eq!(destination, b"abc");
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn other_errors_arent_retried() {
+        let cache = FilePathCache::new(Duration::from_secs(60), 10);
+        let fetches = AtomicUsize::new(0);
+
+        let (res, destination) = download(&cache, &fetches, |_| false).await;
+        assert!(matches!(res, Err(DownloadByFileIdError::Network("404 Not Found"))));
+        assert!(destination.is_empty());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+}
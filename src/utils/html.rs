@@ -2,7 +2,9 @@
 //!
 //! [spec]: https://core.telegram.org/bots/api#html-style
 
-use teloxide_core::types::User;
+use teloxide_core::types::{MessageEntity, MessageEntityKind, User};
+
+use crate::utils::text::render_entities;
 
 /// Applies the bold font style to the string.
 ///
@@ -38,9 +40,9 @@ pub fn strike(s: &str) -> String {
 
 /// Builds an inline link with an anchor.
 ///
-/// Escapes the passed URL and the link text.
+/// Escapes the passed URL (including `"` characters) and the link text.
 pub fn link(url: &str, text: &str) -> String {
-    format!("<a href=\"{}\">{}</a>", escape(url), escape(text))
+    format!("<a href=\"{}\">{}</a>", escape(url).replace("\"", "&quot;"), escape(text))
 }
 
 /// Builds an inline user mention link with an anchor.
@@ -91,6 +93,42 @@ pub fn user_mention_or_link(user: &User) -> String {
     }
 }
 
+/// Renders `text` with `entities` (e.g. of a received message) back into the
+/// HTML markup.
+///
+/// The text is escaped, so the result can be sent with [`ParseMode::Html`] as
+/// is. Entities which Telegram recognizes by itself (mentions, URLs, etc.) are
+/// rendered as plain text.
+///
+/// [`ParseMode::Html`]: crate::types::ParseMode::Html
+pub fn from_entities(text: &str, entities: &[MessageEntity]) -> String {
+    render_entities(text, entities, tags, |s, _| escape(s))
+}
+
+fn tags(kind: &MessageEntityKind) -> Option<(String, String)> {
+    let tags = match kind {
+        MessageEntityKind::Bold => ("<b>".to_owned(), "</b>".to_owned()),
+        MessageEntityKind::Italic => ("<i>".to_owned(), "</i>".to_owned()),
+        MessageEntityKind::Underline => ("<u>".to_owned(), "</u>".to_owned()),
+        MessageEntityKind::Strikethrough => ("<s>".to_owned(), "</s>".to_owned()),
+        MessageEntityKind::Code => ("<code>".to_owned(), "</code>".to_owned()),
+        MessageEntityKind::Pre { language: None } => ("<pre>".to_owned(), "</pre>".to_owned()),
+        MessageEntityKind::Pre { language: Some(language) } => (
+            format!("<pre><code class=\"language-{}\">", escape(language).replace("\"", "&quot;")),
+            "</code></pre>".to_owned(),
+        ),
+        MessageEntityKind::TextLink { url } => {
+            (format!("<a href=\"{}\">", escape(url).replace("\"", "&quot;")), "</a>".to_owned())
+        }
+        MessageEntityKind::TextMention { user } => {
+            (format!("<a href=\"tg://user?id={}\">", user.id), "</a>".to_owned())
+        }
+        _ => return None,
+    };
+
+    Some(tags)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,6 +169,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_link_escapes_quotes_in_url() {
+        assert_eq!(
+            link("https://example.com/?q=\"><b>", "a & b"),
+            "<a href=\"https://example.com/?q=&quot;&gt;&lt;b&gt;\">a &amp; b</a>",
+        );
+    }
+
     #[test]
     fn test_user_mention() {
         assert_eq!(
@@ -204,4 +250,47 @@ mod tests {
             r#"<a href="tg://user/?id=123456789">Name</a>"#
         )
     }
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity { kind, offset, length }
+    }
+
+    #[test]
+    fn from_nested_entities() {
+        let entities = [
+            entity(MessageEntityKind::Italic, 0, 11),
+            entity(MessageEntityKind::Bold, 6, 5),
+            entity(
+                MessageEntityKind::TextLink { url: "https://example.com/?a=1&b=2".to_owned() },
+                14,
+                4,
+            ),
+        ];
+
+        assert_eq!(
+            from_entities("Hello world! <docs>", &entities),
+            r#"<i>Hello <b>world</b></i>! &lt;<a href="https://example.com/?a=1&amp;b=2">docs</a>&gt;"#
+        );
+    }
+
+    #[test]
+    fn from_overlapping_entities() {
+        let entities =
+            [entity(MessageEntityKind::Bold, 0, 6), entity(MessageEntityKind::Italic, 4, 5)];
+
+        assert_eq!(from_entities("foo bar baz", &entities), "<b>foo <i>ba</i></b><i>r b</i>az");
+    }
+
+    #[test]
+    fn from_code_entities() {
+        let entities = [
+            entity(MessageEntityKind::Pre { language: Some("rust".to_owned()) }, 0, 6),
+            entity(MessageEntityKind::Code, 7, 5),
+        ];
+
+        assert_eq!(
+            from_entities("let x; a < b", &entities),
+            r#"<pre><code class="language-rust">let x;</code></pre> <code>a &lt; b</code>"#
+        );
+    }
 }
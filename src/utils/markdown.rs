@@ -2,7 +2,9 @@
 //!
 //! [spec]: https://core.telegram.org/bots/api#markdownv2-style
 
-use teloxide_core::types::User;
+use teloxide_core::types::{MessageEntity, MessageEntityKind, User};
+
+use crate::utils::text::render_entities;
 
 /// Applies the bold font style to the string.
 ///
@@ -53,9 +55,9 @@ pub fn strike(s: &str) -> String {
 
 /// Builds an inline link with an anchor.
 ///
-/// Escapes `)` and ``` characters inside the link url.
+/// Escapes the link text and `)`, ``` and `\` characters inside the link url.
 pub fn link(url: &str, text: &str) -> String {
-    format!("[{}]({})", text, escape_link_url(url))
+    format!("[{}]({})", escape(text), escape_link_url(url))
 }
 
 /// Builds an inline user mention link with an anchor.
@@ -110,9 +112,9 @@ pub fn escape(s: &str) -> String {
 }
 
 /// Escapes all markdown special characters specific for the inline link URL
-/// (``` and `)`).
+/// (```, `)` and `\`).
 pub fn escape_link_url(s: &str) -> String {
-    s.replace("`", r"\`").replace(")", r"\)")
+    s.replace(r"\", r"\\").replace("`", r"\`").replace(")", r"\)")
 }
 
 /// Escapes all markdown special characters specific for the code block (``` and
@@ -128,6 +130,46 @@ pub fn user_mention_or_link(user: &User) -> String {
     }
 }
 
+/// Renders `text` with `entities` (e.g. of a received message) back into the
+/// Markdown V2 markup.
+///
+/// The text is escaped, so the result can be sent with
+/// [`ParseMode::MarkdownV2`] as is. Entities which Telegram recognizes by
+/// itself (mentions, URLs, etc.) are rendered as plain text.
+///
+/// [`ParseMode::MarkdownV2`]: crate::types::ParseMode::MarkdownV2
+pub fn from_entities(text: &str, entities: &[MessageEntity]) -> String {
+    render_entities(
+        text,
+        entities,
+        tags,
+        |s, is_code| if is_code { escape_code(s) } else { escape(s) },
+    )
+}
+
+fn tags(kind: &MessageEntityKind) -> Option<(String, String)> {
+    let tags = match kind {
+        MessageEntityKind::Bold => ("*".to_owned(), "*".to_owned()),
+        MessageEntityKind::Italic => ("_".to_owned(), "_".to_owned()),
+        MessageEntityKind::Underline => ("__".to_owned(), "__".to_owned()),
+        MessageEntityKind::Strikethrough => ("~".to_owned(), "~".to_owned()),
+        MessageEntityKind::Code => ("`".to_owned(), "`".to_owned()),
+        MessageEntityKind::Pre { language } => (
+            format!("```{}\n", language.as_deref().map(escape).unwrap_or_default()),
+            "\n```".to_owned(),
+        ),
+        MessageEntityKind::TextLink { url } => {
+            ("[".to_owned(), format!("]({})", escape_link_url(url)))
+        }
+        MessageEntityKind::TextMention { user } => {
+            ("[".to_owned(), format!("](tg://user?id={})", user.id))
+        }
+        _ => return None,
+    };
+
+    Some(tags)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +217,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_link_escapes_text() {
+        assert_eq!(
+            link("https://example.com/a\\b", "[1.0] *beta* (new)!"),
+            r"[\[1\.0\] \*beta\* \(new\)\!](https://example.com/a\\b)",
+        );
+    }
+
     #[test]
     fn test_user_mention() {
         assert_eq!(user_mention(123_456_789, "pwner666"), "[pwner666](tg://user?id=123456789)");
@@ -222,7 +272,7 @@ mod tests {
             escape_link_url(r"https://en.wikipedia.org/wiki/`"),
             r"https://en.wikipedia.org/wiki/\`"
         );
-        assert_eq!(escape_link_url(r"_*[]()~`#+-=|{}.!\"), r"_*[](\)~\`#+-=|{}.!\");
+        assert_eq!(escape_link_url(r"_*[]()~`#+-=|{}.!\"), r"_*[](\)~\`#+-=|{}.!\\");
     }
 
     #[test]
@@ -255,4 +305,56 @@ mod tests {
             r#"[Name](tg://user/?id=123456789)"#
         )
     }
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity { kind, offset, length }
+    }
+
+    #[test]
+    fn from_nested_entities() {
+        let entities = [
+            entity(MessageEntityKind::Italic, 0, 11),
+            entity(MessageEntityKind::Bold, 6, 5),
+            entity(
+                MessageEntityKind::TextLink { url: "https://example.com/(1)".to_owned() },
+                13,
+                4,
+            ),
+        ];
+
+        assert_eq!(
+            from_entities("Hello world! docs.", &entities),
+            r"_Hello *world*_\! [docs](https://example.com/(1\))\."
+        );
+    }
+
+    #[test]
+    fn from_overlapping_entities() {
+        let entities =
+            [entity(MessageEntityKind::Bold, 0, 6), entity(MessageEntityKind::Italic, 4, 5)];
+
+        assert_eq!(from_entities("foo bar baz", &entities), "*foo _ba_*_r b_az");
+    }
+
+    #[test]
+    fn from_adjacent_underscore_entities() {
+        let entities =
+            [entity(MessageEntityKind::Underline, 0, 3), entity(MessageEntityKind::Italic, 0, 3)];
+
+        assert_eq!(from_entities("foo", &entities), "__\r_foo_\r__");
+    }
+
+    #[test]
+    fn from_code_entities() {
+        let entities = [
+            entity(MessageEntityKind::Code, 0, 3),
+            entity(MessageEntityKind::Pre { language: Some("rust".to_owned()) }, 4, 6),
+            entity(MessageEntityKind::Url, 11, 5),
+        ];
+
+        assert_eq!(
+            from_entities("a_b let x; a.com", &entities),
+            "`a_b` ```rust\nlet x;\n``` a\\.com"
+        );
+    }
 }
@@ -0,0 +1,188 @@
+use serde_json::{json, Value};
+
+use teloxide_core::types::{ForwardKind, ForwardOrigin, Message, MessageKind, PhotoSize};
+
+/// A builder of [`Message`]s for tests and fixtures.
+///
+/// By default, it builds a text message with the empty text, sent by the user
+/// `1` to the private chat with them.
+///
+/// ## Examples
+/// ```
+/// use teloxide::utils::MessageBuilder;
+///
+/// let message = MessageBuilder::new().text("/start").from_user(42).chat(42).build();
+///
+/// assert_eq!(message.text(), Some("/start"));
+/// assert_eq!(message.chat_id(), 42);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    id: i32,
+    date: i32,
+    chat_id: i64,
+    user_id: i64,
+    text: Option<String>,
+    reply_to: Option<Message>,
+    photo: Option<Vec<PhotoSize>>,
+}
+
+impl MessageBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            id: 1,
+            date: 1_600_000_000,
+            chat_id: 1,
+            user_id: 1,
+            text: None,
+            reply_to: None,
+            photo: None,
+        }
+    }
+
+    /// Sets the identifier of the message.
+    #[must_use]
+    pub fn id(mut self, id: i32) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the text of the message, or the caption if it's a photo.
+    #[must_use]
+    pub fn text<T>(mut self, text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Sets the sender of the message.
+    #[must_use]
+    pub fn from_user(mut self, user_id: i64) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    /// Sets the chat of the message.
+    ///
+    /// Positive identifiers are private chats, negative ones are supergroups.
+    #[must_use]
+    pub fn chat(mut self, chat_id: i64) -> Self {
+        self.chat_id = chat_id;
+        self
+    }
+
+    /// Makes the message a reply to `message`.
+    #[must_use]
+    pub fn reply_to(mut self, message: Message) -> Self {
+        self.reply_to = Some(message);
+        self
+    }
+
+    /// Makes the message a photo of `sizes`.
+    #[must_use]
+    pub fn photo(mut self, sizes: Vec<PhotoSize>) -> Self {
+        self.photo = Some(sizes);
+        self
+    }
+
+    /// Builds the message.
+    ///
+    /// # Panics
+    /// Panics if the message cannot be constructed from the specified values.
+    #[must_use]
+    pub fn build(self) -> Message {
+        let chat = if self.chat_id > 0 {
+            json!({ "id": self.chat_id, "type": "private", "first_name": "Test" })
+        } else {
+            json!({ "id": self.chat_id, "type": "supergroup", "title": "Test" })
+        };
+
+        let mut message = json!({
+            "message_id": self.id,
+            "date": self.date,
+            "chat": chat,
+            "from": { "id": self.user_id, "is_bot": false, "first_name": "Test" },
+        });
+
+        let text = self.text.unwrap_or_default();
+        match self.photo {
+            Some(sizes) => {
+                message["photo"] = json!(sizes);
+                message["caption"] = Value::String(text);
+            }
+            None => message["text"] = Value::String(text),
+        }
+
+        let mut message: Message =
+            serde_json::from_value(message).expect("failed to build a message");
+
+        // A serialized `Message` can't always be deserialized back (e.g. the
+        // type of a private chat is serialized as `null`), so the reply is set
+        // after deserializing.
+        if let (Some(reply_to), MessageKind::Common(common)) = (self.reply_to, &mut message.kind) {
+            common.forward_kind =
+                ForwardKind::Origin(ForwardOrigin { reply_to_message: Some(Box::new(reply_to)) });
+        }
+
+        message
+    }
+}
+
+impl Default for MessageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teloxide_core::{
+        requests::{HasPayload, Requester},
+        types::ChatId,
+        Bot,
+    };
+
+    use crate::dispatching::UpdateWithCx;
+
+    fn echo<R>(cx: &UpdateWithCx<R, Message>) -> R::SendMessage
+    where
+        R: Requester,
+    {
+        cx.answer(cx.update.text().unwrap_or_default())
+    }
+
+    #[test]
+    fn text_message_through_handler() {
+        let update = MessageBuilder::new().text("Hello").from_user(42).chat(-100).build();
+        let cx = UpdateWithCx::new(Bot::new("Doesn't matter here"), update);
+
+        let request = echo(&cx);
+        assert_eq!(request.payload_ref().chat_id, ChatId::Id(-100));
+        assert_eq!(request.payload_ref().text, "Hello");
+        assert_eq!(cx.update.from().map(|user| user.id), Some(42));
+    }
+
+    #[test]
+    fn photo_reply() {
+        let size: PhotoSize = serde_json::from_value(json!({
+            "file_id": "id",
+            "file_unique_id": "unique_id",
+            "width": 90,
+            "height": 90
+        }))
+        .unwrap();
+
+        let original = MessageBuilder::new().id(1).text("Send me a photo").build();
+        let message =
+            MessageBuilder::new().id(2).photo(vec![size]).text("Cat").reply_to(original).build();
+
+        assert_eq!(message.photo().map(<[_]>::len), Some(1));
+        assert_eq!(message.caption(), Some("Cat"));
+        assert_eq!(message.reply_to_message().map(|m| m.id), Some(1));
+    }
+}
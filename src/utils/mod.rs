@@ -1,12 +1,40 @@
 //! Some useful utilities.
 
+mod chat_admin;
+mod chat_member_cache;
 pub mod command;
+mod download;
+mod entity_builder;
+mod file_path_cache;
 pub mod html;
 pub mod markdown;
+#[cfg(any(test, feature = "test-utils"))]
+mod message_builder;
+pub mod poll;
+mod request_ext;
+mod text;
+mod ttl_cache;
 mod up_state;
 
 pub use teloxide_core::net::client_from_env;
 
+pub use chat_admin::{is_chat_admin, require_admin, RequireAdminError};
+pub use chat_member_cache::ChatMemberCache;
+pub use download::{
+    download_by_file_id, download_file_limited, download_file_with_progress, DownloadByFileIdError,
+    DownloadStreamError, LimitedDownloadError,
+};
+pub use entity_builder::EntityBuilder;
+pub use file_path_cache::FilePathCache;
+pub use request_ext::RequestExt;
+pub use text::{
+    send_long_message, split_text, truncate_with_entities, MAX_CAPTION_LEN, MAX_MESSAGE_LEN,
+};
+
 #[cfg(feature = "frunk")]
 #[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "frunk")))]
 pub use up_state::UpState;
+
+#[cfg(any(test, feature = "test-utils"))]
+#[cfg_attr(all(docsrs, feature = "nightly"), doc(cfg(feature = "test-utils")))]
+pub use message_builder::MessageBuilder;
@@ -0,0 +1,201 @@
+//! Utils for working with polls.
+//!
+//! [`PollSession`] tallies the answers of quiz polls, e.g. to count the scores
+//! of users during a quiz game. For a single answer, see [`is_correct`].
+
+use std::collections::HashMap;
+
+use teloxide_core::types::PollAnswer;
+
+/// Answers to quiz polls, stored in memory.
+///
+/// Register the quizzes you've sent by [`PollSession::add_quiz`] and feed
+/// [`PollAnswer`] updates (see [`Dispatcher::poll_answers_handler`]) into
+/// [`PollSession::record`]. Answers to unknown polls are ignored.
+///
+/// Note that Telegram sends poll answers only for non-anonymous polls.
+///
+/// ## Examples
+/// ```
+/// use teloxide::{types::PollAnswer, utils::poll::PollSession};
+///
+/// let mut session = PollSession::new();
+/// session.add_quiz("poll", 1);
+///
+/// let answer: PollAnswer = serde_json::from_str(
+///     r#"{"poll_id":"poll","user":{"id":42,"is_bot":false,"first_name":"Ada"},"option_ids":[1]}"#,
+/// )
+/// .unwrap();
+/// session.record(&answer);
+///
+/// assert_eq!(session.correct_count(42), 1);
+/// ```
+///
+/// [`Dispatcher::poll_answers_handler`]: crate::dispatching::Dispatcher::poll_answers_handler
+#[derive(Clone, Debug, Default)]
+pub struct PollSession {
+    quizzes: HashMap<String, Quiz>,
+}
+
+#[derive(Clone, Debug)]
+struct Quiz {
+    correct_option_id: i32,
+    answers: HashMap<i64, Vec<i32>>,
+}
+
+impl PollSession {
+    /// Creates a session without quizzes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts recording answers to the quiz with `poll_id`.
+    ///
+    /// `correct_option_id` is the 0-based identifier of the correct answer
+    /// option, as passed to [`SendPoll`].
+    ///
+    /// [`SendPoll`]: crate::payloads::SendPoll
+    pub fn add_quiz<S>(&mut self, poll_id: S, correct_option_id: i32)
+    where
+        S: Into<String>,
+    {
+        self.quizzes.insert(poll_id.into(), Quiz { correct_option_id, answers: HashMap::new() });
+    }
+
+    /// Records the answer of a user.
+    ///
+    /// A repeated answer of the same user replaces the previous one, and an
+    /// answer without options (i.e. a retracted vote) removes it.
+    pub fn record(&mut self, answer: &PollAnswer) {
+        let quiz = match self.quizzes.get_mut(&answer.poll_id) {
+            Some(quiz) => quiz,
+            None => return,
+        };
+
+        if answer.option_ids.is_empty() {
+            quiz.answers.remove(&answer.user.id);
+        } else {
+            quiz.answers.insert(answer.user.id, answer.option_ids.clone());
+        }
+    }
+
+    /// Returns the number of quizzes answered correctly by the user.
+    #[must_use]
+    pub fn correct_count(&self, user_id: i64) -> usize {
+        self.quizzes
+            .values()
+            .filter(|quiz| {
+                matches!(
+                    quiz.answers.get(&user_id),
+                    Some(ids) if contains_correct(ids, quiz.correct_option_id)
+                )
+            })
+            .count()
+    }
+
+    /// Forgets the quiz with `poll_id` and all the answers to it, e.g. after
+    /// the poll is stopped by [`StopPoll`].
+    ///
+    /// Returns `false` if there was no such quiz.
+    ///
+    /// [`StopPoll`]: crate::payloads::StopPoll
+    pub fn stop_poll(&mut self, poll_id: &str) -> bool {
+        self.quizzes.remove(poll_id).is_some()
+    }
+
+    /// Forgets all the quizzes and answers.
+    pub fn clear(&mut self) {
+        self.quizzes.clear();
+    }
+}
+
+/// Returns `true` if the answer contains the option with
+/// `correct_option_id`, i.e. the [`Poll::correct_option_id`] of the quiz.
+///
+/// A retracted vote (an answer without options) is never correct.
+///
+/// [`Poll::correct_option_id`]: crate::types::Poll::correct_option_id
+#[must_use]
+pub fn is_correct(answer: &PollAnswer, correct_option_id: i32) -> bool {
+    contains_correct(&answer.option_ids, correct_option_id)
+}
+
+fn contains_correct(option_ids: &[i32], correct_option_id: i32) -> bool {
+    option_ids.contains(&correct_option_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::user;
+
+    fn answer(poll_id: &str, user_id: i64, option_ids: &[i32]) -> PollAnswer {
+        serde_json::from_value(serde_json::json!({
+            "poll_id": poll_id,
+            "user": user(user_id),
+            "option_ids": option_ids
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn deserialize_answer() {
+        let answer: PollAnswer = serde_json::from_str(
+            r#"{"poll_id":"poll","user":{"id":42,"is_bot":false,"first_name":"Ada"},"option_ids":[0,2]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(answer.poll_id, "poll");
+        assert_eq!(answer.user.id, 42);
+        assert_eq!(answer.option_ids, vec![0, 2]);
+    }
+
+    #[test]
+    fn correct_answer() {
+        assert!(is_correct(&answer("poll", 10, &[1]), 1));
+        assert!(!is_correct(&answer("poll", 10, &[0]), 1));
+        assert!(!is_correct(&answer("poll", 10, &[]), 1));
+    }
+
+    #[test]
+    fn tally() {
+        let mut session = PollSession::new();
+        session.add_quiz("1", 0);
+        session.add_quiz("2", 2);
+
+        session.record(&answer("1", 10, &[0]));
+        session.record(&answer("2", 10, &[2]));
+        session.record(&answer("1", 20, &[1]));
+        session.record(&answer("2", 20, &[2]));
+        // Unknown polls are ignored.
+        session.record(&answer("3", 20, &[0]));
+
+        assert_eq!(session.correct_count(10), 2);
+        assert_eq!(session.correct_count(20), 1);
+        assert_eq!(session.correct_count(30), 0);
+    }
+
+    #[test]
+    fn retracted_answer_is_removed() {
+        let mut session = PollSession::new();
+        session.add_quiz("1", 0);
+
+        session.record(&answer("1", 10, &[0]));
+        session.record(&answer("1", 10, &[]));
+
+        assert_eq!(session.correct_count(10), 0);
+    }
+
+    #[test]
+    fn stop_poll_clears_answers() {
+        let mut session = PollSession::new();
+        session.add_quiz("1", 0);
+        session.record(&answer("1", 10, &[0]));
+
+        assert!(session.stop_poll("1"));
+        assert!(!session.stop_poll("1"));
+        assert_eq!(session.correct_count(10), 0);
+    }
+}
@@ -0,0 +1,76 @@
+use futures::{future::MapOk, TryFutureExt};
+use teloxide_core::requests::{Output, Request};
+
+/// Extensions for requests.
+pub trait RequestExt: Request {
+    /// Sends the request, discarding its output.
+    ///
+    /// Useful at the end of handlers, which usually return
+    /// [`ResponseResult<()>`]:
+    ///
+    /// ```no_run
+    /// use teloxide::{prelude::*, requests::ResponseResult};
+    ///
+    /// async fn handler(cx: UpdateWithCx<Bot, Message>) -> ResponseResult<()> {
+    ///     cx.answer("Hello!").await_and_ok().await
+    /// }
+    /// ```
+    ///
+    /// [`ResponseResult<()>`]: crate::requests::ResponseResult
+    #[allow(clippy::type_complexity)]
+    fn await_and_ok(self) -> MapOk<Self::Send, fn(Output<Self>)>;
+}
+
+impl<R> RequestExt for R
+where
+    R: Request,
+{
+    fn await_and_ok(self) -> MapOk<Self::Send, fn(Output<Self>)> {
+        self.send().map_ok(drop as fn(Output<Self>))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::future::{ready, Ready};
+    use teloxide_core::{
+        requests::{Payload, ResponseResult},
+        types::Message,
+        RequestError,
+    };
+
+    use crate::utils::MessageBuilder;
+
+    struct FakeSendMessage(Message);
+
+    impl Payload for FakeSendMessage {
+        type Output = Message;
+
+        const NAME: &'static str = "FakeSendMessage";
+    }
+
+    impl Request for FakeSendMessage {
+        type Err = RequestError;
+        type Send = Ready<ResponseResult<Message>>;
+        type SendRef = Ready<ResponseResult<Message>>;
+
+        fn send(self) -> Self::Send {
+            ready(Ok(self.0))
+        }
+
+        fn send_ref(&self) -> Self::SendRef {
+            ready(Ok(self.0.clone()))
+        }
+    }
+
+    async fn handler(message: Message) -> ResponseResult<()> {
+        FakeSendMessage(message).await_and_ok().await
+    }
+
+    #[tokio::test]
+    async fn ends_handler() {
+        assert!(handler(MessageBuilder::new().text("Hello").build()).await.is_ok());
+    }
+}
@@ -0,0 +1,365 @@
+use teloxide_core::{
+    requests::{Request, Requester},
+    types::{ChatId, Message, MessageEntity, MessageEntityKind},
+};
+
+/// The maximum length of a message text in [UTF-16 code units], allowed by
+/// Telegram.
+///
+/// [UTF-16 code units]: https://core.telegram.org/api/entities#entity-length
+pub const MAX_MESSAGE_LEN: usize = 4096;
+
+/// The maximum length of a media caption in [UTF-16 code units], allowed by
+/// Telegram.
+///
+/// [UTF-16 code units]: https://core.telegram.org/api/entities#entity-length
+pub const MAX_CAPTION_LEN: usize = 1024;
+
+/// Splits `text` into chunks of at most `max_len` [UTF-16 code units] each.
+///
+/// A chunk is preferably cut at the last newline, then at the last whitespace
+/// which fits into `max_len`; the delimiter itself is dropped. If a chunk
+/// contains neither, it is cut right at the limit.
+///
+/// # Panics
+/// Panics if `max_len` is less than 2, because such a chunk cannot fit a
+/// character outside the Basic Multilingual Plane.
+///
+/// [UTF-16 code units]: https://core.telegram.org/api/entities#entity-length
+pub fn split_text(text: &str, max_len: usize) -> Vec<&str> {
+    assert!(max_len >= 2, "`max_len` must be at least 2 UTF-16 code units");
+
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while utf16_len(rest) > max_len {
+        let mut len = 0;
+        let mut limit = rest.len();
+        let mut newline = None;
+        let mut whitespace = None;
+
+        for (i, c) in rest.char_indices() {
+            len += c.len_utf16();
+            if len > max_len {
+                limit = i;
+                break;
+            }
+
+            if i != 0 && c == '\n' {
+                newline = Some(i);
+            } else if i != 0 && c.is_whitespace() {
+                whitespace = Some(i);
+            }
+        }
+
+        let (end, next) = match newline.or(whitespace) {
+            Some(i) => (i, i + rest[i..].chars().next().map_or(0, char::len_utf8)),
+            None => (limit, limit),
+        };
+
+        chunks.push(&rest[..end]);
+        rest = &rest[next..];
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+
+    chunks
+}
+
+/// Sends `text` as one or more messages, so that none of them exceeds
+/// [`MAX_MESSAGE_LEN`].
+///
+/// The text is split by [`split_text`] and the chunks are sent sequentially.
+/// If sending of some chunk fails, the error is returned and the rest of the
+/// chunks are not sent.
+pub async fn send_long_message<R, C>(
+    requester: &R,
+    chat_id: C,
+    text: &str,
+) -> Result<Vec<Message>, R::Err>
+where
+    R: Requester,
+    C: Into<ChatId>,
+{
+    let chat_id = chat_id.into();
+    let mut messages = Vec::new();
+
+    for chunk in split_text(text, MAX_MESSAGE_LEN) {
+        messages.push(requester.send_message(chat_id.clone(), chunk).send().await?);
+    }
+
+    Ok(messages)
+}
+
+/// Truncates `text` to at most `max_len` [UTF-16 code units], adjusting
+/// `entities` to the truncated text.
+///
+/// Entities which start after the cut are dropped, and entities which cross
+/// it are clamped to end right at the cut. A character which doesn't fit
+/// entirely is dropped too, so the text may become shorter than `max_len`.
+///
+/// [UTF-16 code units]: https://core.telegram.org/api/entities#entity-length
+pub fn truncate_with_entities<'a>(
+    text: &'a str,
+    entities: &[MessageEntity],
+    max_len: usize,
+) -> (&'a str, Vec<MessageEntity>) {
+    let mut len = 0;
+    let mut end = text.len();
+
+    for (i, c) in text.char_indices() {
+        if len + c.len_utf16() > max_len {
+            end = i;
+            break;
+        }
+        len += c.len_utf16();
+    }
+
+    let entities = entities
+        .iter()
+        .filter(|entity| entity.offset < len)
+        .map(|entity| MessageEntity {
+            length: entity.length.min(len - entity.offset),
+            ..entity.clone()
+        })
+        .filter(|entity| entity.length > 0)
+        .collect();
+
+    (&text[..end], entities)
+}
+
+pub(crate) fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Renders `text` with `entities` into a markup.
+///
+/// `tags` returns the opening and the closing tags of an entity, or `None` if
+/// the entity isn't rendered (e.g. a URL, which is recognized by Telegram
+/// anyway). `escape` escapes a text, which is inside a code entity if the
+/// second argument is `true`.
+///
+/// Nested entities are rendered inside out, i.e. an entity which starts
+/// earlier (or is longer) is opened first. Overlapping entities are rendered
+/// by closing and reopening the inner ones.
+pub(crate) fn render_entities<T, E>(
+    text: &str,
+    entities: &[MessageEntity],
+    tags: T,
+    escape: E,
+) -> String
+where
+    T: Fn(&MessageEntityKind) -> Option<(String, String)>,
+    E: Fn(&str, bool) -> String,
+{
+    let mut spans: Vec<_> = entities
+        .iter()
+        .filter(|entity| entity.length > 0)
+        .filter_map(|entity| {
+            let (open, close) = tags(&entity.kind)?;
+            Some(Span {
+                start: entity.offset,
+                end: entity.offset + entity.length,
+                open,
+                close,
+                is_code: matches!(
+                    entity.kind,
+                    MessageEntityKind::Code | MessageEntityKind::Pre { .. }
+                ),
+            })
+        })
+        .collect();
+    spans.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let mut renderer =
+        Renderer { spans: &spans, out: String::new(), opened: Vec::new(), after_underscore: false };
+    let mut segment = String::new();
+    let mut next = 0;
+    let mut pos = 0;
+
+    for c in text.chars().map(Some).chain(std::iter::once(None)) {
+        if renderer.opened.iter().any(|&i| spans[i].end <= pos)
+            || matches!(spans.get(next), Some(span) if span.start <= pos)
+        {
+            renderer.push_text(&segment, &escape);
+            segment.clear();
+            renderer.close_ended(pos);
+
+            while matches!(spans.get(next), Some(span) if span.start <= pos) {
+                renderer.open(next);
+                next += 1;
+            }
+        }
+
+        match c {
+            Some(c) => {
+                segment.push(c);
+                pos += c.len_utf16();
+            }
+            None => break,
+        }
+    }
+
+    renderer.push_text(&segment, &escape);
+    while let Some(i) = renderer.opened.pop() {
+        renderer.push_tag(&spans[i].close);
+    }
+
+    renderer.out
+}
+
+struct Span {
+    start: usize,
+    end: usize,
+    open: String,
+    close: String,
+    is_code: bool,
+}
+
+struct Renderer<'a> {
+    spans: &'a [Span],
+    out: String,
+    /// Indices of the opened spans, the innermost one is the last.
+    opened: Vec<usize>,
+    after_underscore: bool,
+}
+
+impl Renderer<'_> {
+    fn push_text<E>(&mut self, s: &str, escape: E)
+    where
+        E: Fn(&str, bool) -> String,
+    {
+        if !s.is_empty() {
+            let is_code = self.opened.iter().any(|&i| self.spans[i].is_code);
+            self.out.push_str(&escape(s, is_code));
+            self.after_underscore = false;
+        }
+    }
+
+    fn push_tag(&mut self, tag: &str) {
+        // Markdown V2 treats `___` greedily as the underline tag followed by the
+        // italic tag, so adjacent tags made of underscores are separated by an
+        // ignored `\r` character.
+        if self.after_underscore && tag.starts_with('_') {
+            self.out.push('\r');
+        }
+
+        self.out.push_str(tag);
+        self.after_underscore = tag.ends_with('_');
+    }
+
+    fn open(&mut self, i: usize) {
+        let spans = self.spans;
+        self.push_tag(&spans[i].open);
+        self.opened.push(i);
+    }
+
+    /// Closes the spans which end at `pos`, closing and reopening the spans
+    /// nested into them.
+    fn close_ended(&mut self, pos: usize) {
+        let spans = self.spans;
+        while let Some(depth) = self.opened.iter().position(|&i| spans[i].end <= pos) {
+            let mut reopen = Vec::new();
+
+            while self.opened.len() > depth {
+                let i = self.opened.pop().unwrap();
+                self.push_tag(&spans[i].close);
+
+                if spans[i].end > pos {
+                    reopen.push(i);
+                }
+            }
+
+            for i in reopen.into_iter().rev() {
+                self.open(i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_not_split() {
+        assert_eq!(split_text("foo bar", MAX_MESSAGE_LEN), vec!["foo bar"]);
+        assert!(split_text("", MAX_MESSAGE_LEN).is_empty());
+    }
+
+    #[test]
+    fn long_text_is_split() {
+        let line = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.\n";
+        let text = line.repeat(100);
+        assert!(utf16_len(&text) > MAX_MESSAGE_LEN);
+
+        let chunks = split_text(&text, MAX_MESSAGE_LEN);
+        assert!(chunks.len() >= 2);
+        assert!(chunks.iter().all(|chunk| utf16_len(chunk) <= MAX_MESSAGE_LEN));
+        assert!(chunks.iter().all(|chunk| chunk.trim_end().ends_with('.')));
+    }
+
+    #[test]
+    fn prefers_newlines_then_whitespaces() {
+        assert_eq!(split_text("ab cd\nef gh", 8), vec!["ab cd", "ef gh"]);
+        assert_eq!(split_text("ab cd ef gh", 8), vec!["ab cd", "ef gh"]);
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn counts_utf16_code_units() {
+        // Each emoji takes 2 UTF-16 code units.
+        let text = "😀".repeat(5);
+        let chunks = split_text(&text, 4);
+        assert_eq!(chunks, vec!["😀😀", "😀😀", "😀"]);
+    }
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity { kind, offset, length }
+    }
+
+    #[test]
+    fn truncate_clamps_crossing_entities() {
+        let entities = [
+            entity(MessageEntityKind::Italic, 0, 3),
+            entity(MessageEntityKind::Bold, 4, 6),
+            entity(MessageEntityKind::Code, 8, 2),
+        ];
+
+        let (text, entities) = truncate_with_entities("foo barbaz", &entities, 6);
+        assert_eq!(text, "foo ba");
+        assert_eq!(
+            entities,
+            vec![entity(MessageEntityKind::Italic, 0, 3), entity(MessageEntityKind::Bold, 4, 2)]
+        );
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        let entities = [entity(MessageEntityKind::Bold, 0, 3)];
+
+        let (text, truncated) = truncate_with_entities("foo", &entities, MAX_CAPTION_LEN);
+        assert_eq!(text, "foo");
+        assert_eq!(truncated, entities);
+    }
+
+    #[test]
+    fn truncate_to_empty() {
+        let entities = [entity(MessageEntityKind::Bold, 0, 3)];
+
+        let (text, entities) = truncate_with_entities("foo", &entities, 0);
+        assert_eq!(text, "");
+        assert!(entities.is_empty());
+    }
+
+    #[test]
+    fn truncate_doesnt_split_surrogate_pairs() {
+        let entities = [entity(MessageEntityKind::Bold, 0, 4)];
+
+        let (text, entities) = truncate_with_entities("😀😀", &entities, 3);
+        assert_eq!(text, "😀");
+        assert_eq!(entities, vec![entity(MessageEntityKind::Bold, 0, 2)]);
+    }
+}
@@ -2,7 +2,9 @@ use std::{
     fmt::{Debug, Display},
     sync::Arc,
 };
-use teloxide::dispatching::dialogue::{RedisStorage, RedisStorageError, Serializer, Storage};
+use teloxide::dispatching::dialogue::{
+    RedisStorage, RedisStorageError, Serializer, Storage, StorageBatch, StorageCas,
+};
 
 #[tokio::test]
 async fn test_redis_json() {
@@ -37,6 +39,43 @@ async fn test_redis_cbor() {
     test_redis(storage).await;
 }
 
+#[tokio::test]
+async fn test_redis_prefix() {
+    let staging = RedisStorage::open_with_prefix(
+        "redis://127.0.0.1:7777",
+        teloxide::dispatching::dialogue::serializer::Json,
+        "staging",
+    )
+    .await
+    .unwrap();
+    let prod = RedisStorage::open_with_prefix(
+        "redis://127.0.0.1:7777",
+        teloxide::dispatching::dialogue::serializer::Json,
+        "prod",
+    )
+    .await
+    .unwrap();
+    test_prefix(staging, prod).await;
+}
+
+async fn test_prefix<S>(staging: Arc<RedisStorage<S>>, prod: Arc<RedisStorage<S>>)
+where
+    S: Send + Sync + Serializer<Dialogue> + 'static,
+    <S as Serializer<Dialogue>>::Error: Debug + Display,
+{
+    Arc::clone(&staging).update_dialogue(1, "ABC".to_owned()).await.unwrap();
+    Arc::clone(&prod).update_dialogue(1, "DEF".to_owned()).await.unwrap();
+
+    assert_eq!(Arc::clone(&staging).get_dialogue(1).await.unwrap(), Some("ABC".to_owned()));
+    assert_eq!(Arc::clone(&prod).get_dialogue(1).await.unwrap(), Some("DEF".to_owned()));
+
+    Arc::clone(&staging).remove_dialogue(1).await.unwrap();
+    assert_eq!(Arc::clone(&staging).get_dialogue(1).await.unwrap(), None::<Dialogue>);
+    assert_eq!(Arc::clone(&prod).get_dialogue(1).await.unwrap(), Some("DEF".to_owned()));
+
+    Arc::clone(&prod).remove_dialogue(1).await.unwrap();
+}
+
 type Dialogue = String;
 
 macro_rules! test_dialogues {
@@ -76,4 +115,38 @@ where
         Arc::clone(&storage).remove_dialogue(1).await.unwrap_err(),
         RedisStorageError::DialogueNotFound
     ));
+
+    // Check that batch operations work in one call.
+    Arc::clone(&storage)
+        .update_dialogues(vec![
+            (1, "ABC".to_owned()),
+            (11, "DEF".to_owned()),
+            (256, "GHI".to_owned()),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Arc::clone(&storage).get_dialogues(vec![1, 11, 256, 2]).await.unwrap(),
+        vec![Some("ABC".to_owned()), Some("DEF".to_owned()), Some("GHI".to_owned()), None]
+    );
+
+    Arc::clone(&storage).remove_dialogue(1).await.unwrap();
+    Arc::clone(&storage).remove_dialogue(11).await.unwrap();
+    Arc::clone(&storage).remove_dialogue(256).await.unwrap();
+
+    // Check that a stale compare-and-swap fails.
+    assert!(Arc::clone(&storage).compare_and_swap(1, None, "ABC".to_owned()).await.unwrap());
+    assert!(!Arc::clone(&storage).compare_and_swap(1, None, "DEF".to_owned()).await.unwrap());
+    assert!(Arc::clone(&storage)
+        .compare_and_swap(1, Some("ABC".to_owned()), "DEF".to_owned())
+        .await
+        .unwrap());
+    assert!(!Arc::clone(&storage)
+        .compare_and_swap(1, Some("ABC".to_owned()), "GHI".to_owned())
+        .await
+        .unwrap());
+    assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("DEF".to_owned()));
+
+    Arc::clone(&storage).remove_dialogue(1).await.unwrap();
 }
@@ -2,7 +2,9 @@ use std::{
     fmt::{Debug, Display},
     sync::Arc,
 };
-use teloxide::dispatching::dialogue::{Serializer, SqliteStorage, SqliteStorageError, Storage};
+use teloxide::dispatching::dialogue::{
+    Serializer, SqliteStorage, SqliteStorageError, Storage, StorageCas,
+};
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_sqlite_json() {
@@ -72,4 +74,19 @@ where
         Arc::clone(&storage).remove_dialogue(1).await.unwrap_err(),
         SqliteStorageError::DialogueNotFound
     ));
+
+    // Check that a stale compare-and-swap fails.
+    assert!(Arc::clone(&storage).compare_and_swap(1, None, "ABC".to_owned()).await.unwrap());
+    assert!(!Arc::clone(&storage).compare_and_swap(1, None, "DEF".to_owned()).await.unwrap());
+    assert!(Arc::clone(&storage)
+        .compare_and_swap(1, Some("ABC".to_owned()), "DEF".to_owned())
+        .await
+        .unwrap());
+    assert!(!Arc::clone(&storage)
+        .compare_and_swap(1, Some("ABC".to_owned()), "GHI".to_owned())
+        .await
+        .unwrap());
+    assert_eq!(Arc::clone(&storage).get_dialogue(1).await.unwrap(), Some("DEF".to_owned()));
+
+    Arc::clone(&storage).remove_dialogue(1).await.unwrap();
 }